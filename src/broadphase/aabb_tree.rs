@@ -0,0 +1,487 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::hash::Hash;
+
+use bevy::{math::Vec2, utils::HashMap};
+
+use crate::quadtree::{ray_aabb, Element, RayHit, Rect};
+use super::{Broadphase, HitTest};
+
+/*
+    ---
+    SOURCES
+    https://box2d.org/files/ErinCatto_DynamicBVH_GDC2019.pdf
+    https://github.com/erincatto/box2d/blob/main/src/dynamic_tree.c
+    ---
+*/
+
+#[derive(Debug, Clone)]
+pub struct AabbNode<T> {
+    //leaves hold their element's bounds grown by the margin, branches the union of their children
+    pub fat: Rect,
+    pub parent: Option<usize>,
+    pub children: Option<[usize; 2]>,
+    pub item: Option<T>,
+    //0 for leaves
+    pub height: usize,
+}
+
+//binary tree of bounding boxes, every element gets its own leaf and the tree is kept balanced by rotations
+#[derive(Debug)]
+pub struct DynamicAabbTree<T> {
+    pub nodes: Vec<AabbNode<T>>,
+    free: Vec<usize>,
+    pub root: Option<usize>,
+    //fat bounds are grown by this much so small moves don't touch the tree
+    pub margin: f32,
+    leaves: HashMap<T, usize>,
+    pub tracked: HashMap<T, Rect>,
+}
+
+impl<T: Copy + Eq + Hash> DynamicAabbTree<T> {
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            margin: margin.max(0.),
+            leaves: HashMap::new(),
+            tracked: HashMap::new(),
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.root.map_or(0, |root| self.nodes[root].height)
+    }
+
+    fn alloc(&mut self, fat: Rect, item: Option<T>) -> usize {
+        let node = AabbNode { fat: fat, parent: None, children: None, item: item, height: 0 };
+        if let Some(index) = self.free.pop() {
+            self.nodes[index] = node;
+            return index;
+        }
+        self.nodes.push(node);
+        return self.nodes.len() - 1;
+    }
+
+    fn fatten(&self, rect: &Rect) -> Rect {
+        rect.grow(self.margin)
+    }
+
+    //swaps the link from parent to old_child over to new_child, or makes it the root
+    fn replace_child(&mut self, parent: Option<usize>, old_child: usize, new_child: usize) {
+        let Some(parent) = parent else {
+            self.root = Some(new_child);
+            return;
+        };
+        let children = self.nodes[parent].children.as_mut().unwrap();
+        if children[0] == old_child {
+            children[0] = new_child;
+        } else {
+            children[1] = new_child;
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(root) = self.root else {
+            self.root = Some(leaf);
+            self.nodes[leaf].parent = None;
+            return;
+        };
+
+        //walk down towards the sibling that grows the tree's surface the least
+        let leaf_fat = self.nodes[leaf].fat;
+        let mut index = root;
+        while let Some([child_a, child_b]) = self.nodes[index].children {
+            let perimeter = self.nodes[index].fat.perimeter();
+            let combined = self.nodes[index].fat.union(&leaf_fat).perimeter();
+
+            //pairing with this node means a new parent here, going lower still grows every ancestor
+            let cost = 2. * combined;
+            let inheritance = 2. * (combined - perimeter);
+            let child_cost = |child: usize| {
+                let node = &self.nodes[child];
+                let grown = node.fat.union(&leaf_fat).perimeter();
+                if node.children.is_none() {
+                    grown + inheritance
+                } else {
+                    grown - node.fat.perimeter() + inheritance
+                }
+            };
+            let cost_a = child_cost(child_a);
+            let cost_b = child_cost(child_b);
+
+            if cost < cost_a && cost < cost_b {
+                break;
+            }
+            index = if cost_a < cost_b { child_a } else { child_b };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.alloc(leaf_fat.union(&self.nodes[sibling].fat), None);
+        self.nodes[new_parent].parent = old_parent;
+        self.nodes[new_parent].children = Some([sibling, leaf]);
+        self.nodes[new_parent].height = self.nodes[sibling].height + 1;
+
+        self.replace_child(old_parent, sibling, new_parent);
+        self.nodes[sibling].parent = Some(new_parent);
+        self.nodes[leaf].parent = Some(new_parent);
+
+        self.refit(Some(new_parent));
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        let Some(parent) = self.nodes[leaf].parent else {
+            self.root = None;
+            return;
+        };
+
+        //the parent goes away and the sibling takes its place
+        let [child_a, child_b] = self.nodes[parent].children.unwrap();
+        let sibling = if child_a == leaf { child_b } else { child_a };
+        let grandparent = self.nodes[parent].parent;
+
+        self.replace_child(grandparent, parent, sibling);
+        self.nodes[sibling].parent = grandparent;
+        self.free.push(parent);
+
+        self.refit(grandparent);
+    }
+
+    //fixes heights and bounds from index up to the root, balancing on the way
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(current) = index {
+            let current = self.balance(current);
+            let [child_a, child_b] = self.nodes[current].children.unwrap();
+            self.nodes[current].height = 1 + self.nodes[child_a].height.max(self.nodes[child_b].height);
+            self.nodes[current].fat = self.nodes[child_a].fat.union(&self.nodes[child_b].fat);
+            index = self.nodes[current].parent;
+        }
+    }
+
+    //rotates the taller grandchild up when a's children differ in height by more than one, returns the node now in a's place
+    fn balance(&mut self, a: usize) -> usize {
+        let Some([b, c]) = self.nodes[a].children else {
+            return a;
+        };
+        if self.nodes[a].height < 2 {
+            return a;
+        }
+
+        let height_b = self.nodes[b].height as isize;
+        let height_c = self.nodes[c].height as isize;
+        if height_c - height_b > 1 {
+            return self.rotate_up(a, c, 1);
+        }
+        if height_b - height_c > 1 {
+            return self.rotate_up(a, b, 0);
+        }
+        return a;
+    }
+
+    //lifts child (found at slot of a's children) above a, a keeps the shorter of child's children
+    fn rotate_up(&mut self, a: usize, child: usize, slot: usize) -> usize {
+        let other = self.nodes[a].children.unwrap()[1 - slot];
+        let [f, g] = self.nodes[child].children.unwrap();
+
+        let a_parent = self.nodes[a].parent;
+        self.nodes[child].parent = a_parent;
+        self.nodes[a].parent = Some(child);
+        self.replace_child(a_parent, a, child);
+
+        let (keep, give) = if self.nodes[f].height > self.nodes[g].height { (f, g) } else { (g, f) };
+        self.nodes[child].children = Some([a, keep]);
+        self.nodes[a].children.as_mut().unwrap()[slot] = give;
+        self.nodes[give].parent = Some(a);
+
+        self.nodes[a].fat = self.nodes[other].fat.union(&self.nodes[give].fat);
+        self.nodes[a].height = 1 + self.nodes[other].height.max(self.nodes[give].height);
+        self.nodes[child].fat = self.nodes[a].fat.union(&self.nodes[keep].fat);
+        self.nodes[child].height = 1 + self.nodes[a].height.max(self.nodes[keep].height);
+        return child;
+    }
+}
+
+impl<T: Copy + Eq + Hash> Broadphase<T> for DynamicAabbTree<T> {
+    fn insert(&mut self, item: &T, rect: &Rect) -> bool {
+        if self.tracked.contains_key(item) {
+            return self.update(item, rect);
+        }
+
+        let leaf = self.alloc(self.fatten(rect), Some(*item));
+        self.insert_leaf(leaf);
+        self.leaves.insert(*item, leaf);
+        self.tracked.insert(*item, *rect);
+        return true;
+    }
+
+    //the tree only changes once the element leaves its fat bounds
+    fn update(&mut self, item: &T, rect: &Rect) -> bool {
+        let Some(leaf) = self.leaves.get(item).copied() else {
+            return self.insert(item, rect);
+        };
+
+        self.tracked.insert(*item, *rect);
+        if self.nodes[leaf].fat.encloses(rect) {
+            return true;
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].fat = self.fatten(rect);
+        self.insert_leaf(leaf);
+        return true;
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        let Some(leaf) = self.leaves.remove(item) else {
+            return false;
+        };
+        self.tracked.remove(item);
+        self.remove_leaf(leaf);
+        self.free.push(leaf);
+        return true;
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = None;
+        self.leaves.clear();
+        self.tracked.clear();
+    }
+
+    fn get(&self, item: &T) -> Option<Rect> {
+        self.tracked.get(item).copied()
+    }
+
+    fn elements(&self) -> Vec<Element<T>> {
+        self.tracked.iter().map(|(item, rect)| (*item, *rect)).collect()
+    }
+
+    fn query_area(&self, rect: &Rect) -> Vec<Element<T>> {
+        let mut elements = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.fat.intersects(rect) {
+                continue;
+            }
+            match (node.children, node.item) {
+                (Some(children), _) => stack.extend(children),
+                (None, Some(item)) => {
+                    let bounds = self.tracked[&item];
+                    if bounds.intersects(rect) {
+                        elements.push((item, bounds));
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        return elements;
+    }
+
+    fn raycast_with(&self, origin: Vec2, dir: Vec2, max_dist: f32, hit_test: HitTest<T>) -> Option<RayHit<T>> {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec2::ZERO {
+            return None;
+        }
+
+        let mut best: Option<(T, f32, Vec2)> = None;
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(index) = stack.pop() {
+            //anything entered past the current hit can't beat it
+            let limit = best.map_or(max_dist, |(_, distance, _)| distance);
+            let node = &self.nodes[index];
+            if ray_aabb(origin, dir, &node.fat, limit).is_none() {
+                continue;
+            }
+
+            match (node.children, node.item) {
+                (Some(children), _) => stack.extend(children),
+                (None, Some(item)) => {
+                    let bounds = self.tracked[&item];
+                    let Some((distance, normal)) = hit_test(&item, &bounds) else {
+                        continue;
+                    };
+                    if distance <= max_dist && best.is_none_or(|(_, best_distance, _)| distance < best_distance) {
+                        best = Some((item, distance, normal));
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+
+        return best.map(|(item, distance, normal)| RayHit {
+            item: item,
+            point: origin + dir * distance,
+            normal: normal,
+            distance: distance,
+        });
+    }
+
+    fn debug_rects(&self) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(index) = stack.pop() {
+            rects.push(self.nodes[index].fat);
+            stack.extend(self.nodes[index].children.into_iter().flatten());
+        }
+        return rects;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_rect(rng: &mut StdRng) -> Rect {
+        let center = Vec2::new(rng.gen_range(0.0..1000.), rng.gen_range(-800.0..0.));
+        let half_size = Vec2::new(rng.gen_range(1.0..30.), rng.gen_range(1.0..30.));
+        Rect::from_center(center, half_size)
+    }
+
+    //walks the whole tree checking its links, heights and bounds, returns how many leaves it reached
+    fn check_invariants(tree: &DynamicAabbTree<u32>) -> usize {
+        let Some(root) = tree.root else {
+            assert!(tree.tracked.is_empty());
+            return 0;
+        };
+        assert_eq!(tree.nodes[root].parent, None);
+
+        let mut leaves = 0;
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            let node = &tree.nodes[index];
+            match node.children {
+                Some([a, b]) => {
+                    assert!(node.item.is_none());
+                    assert_eq!(tree.nodes[a].parent, Some(index));
+                    assert_eq!(tree.nodes[b].parent, Some(index));
+                    assert_eq!(node.height, 1 + tree.nodes[a].height.max(tree.nodes[b].height));
+                    assert!(node.fat.encloses(&tree.nodes[a].fat));
+                    assert!(node.fat.encloses(&tree.nodes[b].fat));
+                    stack.extend([a, b]);
+                }
+                None => {
+                    let item = node.item.expect("leaf without an item");
+                    assert_eq!(node.height, 0);
+                    assert_eq!(tree.leaves[&item], index);
+                    assert!(node.fat.encloses(&tree.tracked[&item]));
+                    leaves += 1;
+                }
+            }
+        }
+        assert_eq!(leaves, tree.tracked.len());
+        return leaves;
+    }
+
+    fn sorted(mut elements: Vec<Element<u32>>) -> Vec<u32> {
+        let mut items: Vec<u32> = elements.drain(..).map(|(item, _)| item).collect();
+        items.sort_unstable();
+        return items;
+    }
+
+    //inserts, moves by small and large steps, removes and reinserts, checking the tree after every batch
+    fn churned_tree(seed: u64) -> (DynamicAabbTree<u32>, StdRng) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tree = DynamicAabbTree::new(3.);
+        for item in 0..300 {
+            tree.insert(&item, &random_rect(&mut rng));
+        }
+        check_invariants(&tree);
+
+        for round in 0..20 {
+            for _ in 0..100 {
+                let item = rng.gen_range(0..400);
+                match rng.gen_range(0..4) {
+                    0 => {
+                        tree.remove(&item);
+                    }
+                    1 => {
+                        tree.update(&item, &random_rect(&mut rng));
+                    }
+                    _ => {
+                        //mostly small moves, which the fat margin should absorb
+                        let Some(rect) = tree.get(&item) else {
+                            continue;
+                        };
+                        let step = Vec2::new(rng.gen_range(-2.0..2.), rng.gen_range(-2.0..2.));
+                        tree.update(&item, &Rect::from_center(rect.center() + step, rect.half_size));
+                    }
+                }
+            }
+            let leaves = check_invariants(&tree);
+            //rotations keep it from degrading towards a list
+            let bound = 2 * ((leaves + 1) as f32).log2().ceil() as usize + 2;
+            assert!(tree.height() <= bound, "round {round} height {} for {leaves} leaves", tree.height());
+        }
+        return (tree, rng);
+    }
+
+    #[test]
+    fn keeps_invariants_through_churn() {
+        for seed in 0..4 {
+            churned_tree(seed);
+        }
+    }
+
+    #[test]
+    fn query_area_matches_brute_force() {
+        let (tree, mut rng) = churned_tree(7);
+        for _ in 0..200 {
+            let area = random_rect(&mut rng).grow(rng.gen_range(0.0..60.));
+            let expected: Vec<u32> = {
+                let mut items: Vec<u32> = tree.tracked.iter()
+                    .filter(|(_, bounds)| bounds.intersects(&area))
+                    .map(|(item, _)| *item)
+                    .collect();
+                items.sort_unstable();
+                items
+            };
+            assert_eq!(sorted(tree.query_area(&area)), expected);
+        }
+    }
+
+    #[test]
+    fn raycast_matches_brute_force() {
+        let (tree, mut rng) = churned_tree(11);
+        for _ in 0..200 {
+            let origin = Vec2::new(rng.gen_range(-100.0..1100.), rng.gen_range(-900.0..100.));
+            let dir = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU));
+            let max_dist = rng.gen_range(50.0..1500.);
+
+            let expected = tree.tracked.values()
+                .filter_map(|bounds| ray_aabb(origin, dir, bounds, max_dist).map(|(distance, _)| distance))
+                .fold(None, |best: Option<f32>, distance| Some(best.map_or(distance, |best| best.min(distance))));
+            //the cast renormalizes dir, so distances can differ in the last bits
+            let hit = tree.raycast(origin, dir, max_dist);
+            assert_eq!(hit.is_some(), expected.is_some());
+            if let (Some(hit), Some(expected)) = (hit, expected) {
+                assert!((hit.distance - expected).abs() < 0.001, "hit at {} expected {expected}", hit.distance);
+                let bounds = tree.tracked[&hit.item];
+                assert!(ray_aabb(origin, dir, &bounds, max_dist).is_some_and(|(distance, _)| (distance - hit.distance).abs() < 0.001));
+            }
+        }
+    }
+
+    #[test]
+    fn small_moves_stay_in_the_fat_bounds() {
+        let mut tree = DynamicAabbTree::new(5.);
+        let rect = Rect::from_center(Vec2::new(100., -100.), Vec2::splat(10.));
+        tree.insert(&0, &rect);
+        tree.insert(&1, &Rect::from_center(Vec2::new(300., -100.), Vec2::splat(10.)));
+        let fat = tree.nodes[tree.leaves[&0]].fat;
+
+        tree.update(&0, &Rect::from_center(Vec2::new(104., -100.), Vec2::splat(10.)));
+        assert_eq!(tree.nodes[tree.leaves[&0]].fat, fat);
+        assert_eq!(tree.get(&0).map(|bounds| bounds.center()), Some(Vec2::new(104., -100.)));
+
+        tree.update(&0, &Rect::from_center(Vec2::new(120., -100.), Vec2::splat(10.)));
+        assert_ne!(tree.nodes[tree.leaves[&0]].fat, fat);
+        check_invariants(&tree);
+    }
+}
@@ -0,0 +1,158 @@
+#![allow(clippy::needless_return)]
+
+use std::hash::Hash;
+
+use bevy::{math::{IVec2, Vec2}, utils::HashMap};
+
+use crate::quadtree::{Element, Rect};
+use super::{dedup_elements, Broadphase};
+
+//uniform cells hashed by coordinate, each element is stored in every cell its bounds touch
+#[derive(Debug)]
+pub struct SpatialHashGrid<T> {
+    pub cell_size: f32,
+    pub cells: HashMap<IVec2, Vec<Element<T>>>,
+    pub tracked: HashMap<T, Rect>,
+}
+
+impl<T: Copy + Eq + Hash> SpatialHashGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(1.),
+            cells: HashMap::new(),
+            tracked: HashMap::new(),
+        }
+    }
+
+    pub fn cell_of(&self, p: Vec2) -> IVec2 {
+        (p / self.cell_size).floor().as_ivec2()
+    }
+
+    //first and last cell rect covers, inclusive
+    fn cell_range(&self, rect: &Rect) -> (IVec2, IVec2) {
+        (self.cell_of(Vec2::new(rect.left, rect.bottom)), self.cell_of(Vec2::new(rect.right, rect.top)))
+    }
+
+    pub fn cell_rect(&self, cell: IVec2) -> Rect {
+        let size = Vec2::splat(self.cell_size);
+        Rect::new(Vec2::new(cell.x as f32, cell.y as f32 + 1.) * self.cell_size, size)
+    }
+
+    fn insert_element(&mut self, item: &T, rect: &Rect) {
+        let (min, max) = self.cell_range(rect);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells.entry(IVec2::new(x, y)).or_default().push((*item, *rect));
+            }
+        }
+    }
+
+    fn remove_element(&mut self, item: &T, rect: &Rect) {
+        let (min, max) = self.cell_range(rect);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let cell = IVec2::new(x, y);
+                let Some(elements) = self.cells.get_mut(&cell) else {
+                    continue;
+                };
+                if let Some(index) = elements.iter().position(|(e, _)| *e == *item) {
+                    elements.swap_remove(index);
+                }
+                if elements.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Broadphase<T> for SpatialHashGrid<T> {
+    fn insert(&mut self, item: &T, rect: &Rect) -> bool {
+        if self.tracked.contains_key(item) {
+            return self.update(item, rect);
+        }
+        self.insert_element(item, rect);
+        self.tracked.insert(*item, *rect);
+        return true;
+    }
+
+    //only touches the cells when the element moved into different ones
+    fn update(&mut self, item: &T, rect: &Rect) -> bool {
+        let Some(old_rect) = self.tracked.get(item).copied() else {
+            return self.insert(item, rect);
+        };
+
+        let old_range = self.cell_range(&old_rect);
+        let new_range = self.cell_range(rect);
+        if old_range == new_range {
+            let (min, max) = new_range;
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    let Some(elements) = self.cells.get_mut(&IVec2::new(x, y)) else {
+                        continue;
+                    };
+                    if let Some(element) = elements.iter_mut().find(|(e, _)| *e == *item) {
+                        element.1 = *rect;
+                    }
+                }
+            }
+        } else {
+            self.remove_element(item, &old_rect);
+            self.insert_element(item, rect);
+        }
+        self.tracked.insert(*item, *rect);
+        return true;
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        let Some(rect) = self.tracked.remove(item) else {
+            return false;
+        };
+        self.remove_element(item, &rect);
+        return true;
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+        self.tracked.clear();
+    }
+
+    fn get(&self, item: &T) -> Option<Rect> {
+        self.tracked.get(item).copied()
+    }
+
+    fn elements(&self) -> Vec<Element<T>> {
+        self.tracked.iter().map(|(item, rect)| (*item, *rect)).collect()
+    }
+
+    fn query_area(&self, rect: &Rect) -> Vec<Element<T>> {
+        let mut elements = Vec::new();
+        let (min, max) = self.cell_range(rect);
+        let covered = (max.x - min.x + 1) as i64 * (max.y - min.y + 1) as i64;
+
+        //a query bigger than the occupied cells is cheaper done the other way round
+        if covered > self.cells.len() as i64 {
+            for (cell, cell_elements) in self.cells.iter() {
+                if cell.cmpge(min).all() && cell.cmple(max).all() {
+                    elements.extend(cell_elements.iter().filter(|(_, bounds)| bounds.intersects(rect)));
+                }
+            }
+        } else {
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    let Some(cell_elements) = self.cells.get(&IVec2::new(x, y)) else {
+                        continue;
+                    };
+                    elements.extend(cell_elements.iter().filter(|(_, bounds)| bounds.intersects(rect)));
+                }
+            }
+        }
+
+        dedup_elements(&mut elements);
+        return elements;
+    }
+
+    fn debug_rects(&self) -> Vec<Rect> {
+        self.cells.keys().map(|cell| self.cell_rect(*cell)).collect()
+    }
+}
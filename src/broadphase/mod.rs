@@ -0,0 +1,502 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::hash::Hash;
+
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+
+use crate::quadtree::{
+    ray_aabb, ray_circle, sweep_aabb, sweep_circle, CastShape, Element, QuadTree, RayHit, Rect, ShapeHit,
+    TreeSettings, ROOT,
+};
+
+pub mod grid;
+pub use grid::*;
+pub mod aabb_tree;
+pub use aabb_tree::*;
+pub mod sap;
+pub use sap::*;
+
+const EPSILON: f32 = 0.0001;
+
+/*
+    ---
+    every spatial index the solver can run on, they all store T with its bounds
+    and answer the same queries so scenes can pick whichever is fastest for them
+    ---
+*/
+
+pub type HitTest<'a, T> = &'a mut dyn FnMut(&T, &Rect) -> Option<(f32, Vec2)>;
+
+pub trait Broadphase<T: Copy + Eq + Hash> {
+    fn insert(&mut self, item: &T, rect: &Rect) -> bool;
+    //inserts item when it isn't tracked yet
+    fn update(&mut self, item: &T, rect: &Rect) -> bool;
+    fn remove(&mut self, item: &T) -> bool;
+    fn clear(&mut self);
+    //the bounds item was last inserted or updated with
+    fn get(&self, item: &T) -> Option<Rect>;
+    //every tracked item with its bounds
+    fn elements(&self) -> Vec<Element<T>>;
+    //every element whose bounds touch rect, each reported once
+    fn query_area(&self, rect: &Rect) -> Vec<Element<T>>;
+
+    //every element whose circle, the one inscribed in its bounds, comes within radius of center, each reported once
+    fn query_radius(&self, center: Vec2, radius: f32) -> Vec<Element<T>> {
+        let mut elements = self.query_area(&Rect::from_center(center, Vec2::splat(radius)));
+        elements.retain(|(_, bounds)| bounds.circle_distance_to(center) <= radius);
+        return elements;
+    }
+
+    //hit_test does the exact shape test for an element, returning distance and normal, or None to skip it
+    fn raycast_with(&self, origin: Vec2, dir: Vec2, max_dist: f32, hit_test: HitTest<T>) -> Option<RayHit<T>> {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec2::ZERO {
+            return None;
+        }
+
+        //no structure to walk, so test everything along the segment's bounds
+        let end = origin + dir * max_dist;
+        let segment = Rect::new(Vec2::new(origin.x.min(end.x), origin.y.max(end.y)), (end - origin).abs());
+
+        let mut best: Option<(T, f32, Vec2)> = None;
+        for (item, bounds) in self.query_area(&segment).iter() {
+            let Some((distance, normal)) = hit_test(item, bounds) else {
+                continue;
+            };
+            if distance <= max_dist && best.is_none_or(|(_, best_distance, _)| distance < best_distance) {
+                best = Some((*item, distance, normal));
+            }
+        }
+
+        return best.map(|(item, distance, normal)| RayHit {
+            item: item,
+            point: origin + dir * distance,
+            normal: normal,
+            distance: distance,
+        });
+    }
+
+    //first element bounds hit along the ray
+    fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<RayHit<T>> {
+        let dir = dir.normalize_or_zero();
+        self.raycast_with(origin, dir, max_dist, &mut |_, bounds| ray_aabb(origin, dir, bounds, max_dist))
+    }
+
+    //hit_test does the exact sweep against an element, returning distance along the motion and normal, or None to skip it
+    fn shape_cast_with(&self, shape: &CastShape, origin: Vec2, motion: Vec2, hit_test: HitTest<T>) -> Option<ShapeHit<T>> {
+        let max_dist = motion.length();
+        let dir = motion.normalize_or_zero();
+
+        //everything the shape could touch lies inside the bounds it sweeps through
+        let start = shape.bounds(origin);
+        let end = shape.bounds(origin + motion);
+        let min = Vec2::new(start.left.min(end.left), start.bottom.min(end.bottom));
+        let max = Vec2::new(start.right.max(end.right), start.top.max(end.top));
+        let swept = Rect::new(Vec2::new(min.x, max.y), max - min);
+
+        let mut best: Option<(T, f32, Vec2, Rect)> = None;
+        for (item, bounds) in self.query_area(&swept).iter() {
+            let Some((distance, normal)) = hit_test(item, bounds) else {
+                continue;
+            };
+            if distance <= max_dist && best.is_none_or(|(_, best_distance, _, _)| distance < best_distance) {
+                best = Some((*item, distance, normal, *bounds));
+            }
+        }
+
+        return best.map(|(item, distance, normal, bounds)| {
+            let center = origin + dir * distance;
+            let toi = if max_dist > EPSILON { distance / max_dist } else { 0. };
+            ShapeHit {
+                item: item,
+                toi: toi,
+                center: center,
+                //closest point of what was hit to where the shape ended up
+                point: Vec2::new(center.x.clamp(bounds.left, bounds.right), center.y.clamp(bounds.bottom, bounds.top)),
+                normal: normal,
+            }
+        });
+    }
+
+    //earliest element bounds the shape touches while moving from origin by motion
+    fn shape_cast(&self, shape: &CastShape, origin: Vec2, motion: Vec2) -> Option<ShapeHit<T>> {
+        let max_dist = motion.length();
+        let dir = motion.normalize_or_zero();
+        self.shape_cast_with(shape, origin, motion, &mut |_, bounds| sweep_aabb(shape, origin, dir, bounds, max_dist))
+    }
+
+    //elements stored as a circle's bounds, cast against the circle rather than the box
+    fn raycast_points(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<RayHit<T>> {
+        let dir = dir.normalize_or_zero();
+        self.raycast_with(origin, dir, max_dist, &mut |_, bounds| {
+            ray_circle(origin, dir, bounds.center(), bounds.half_size.x, max_dist)
+        })
+    }
+
+    //earliest circle the shape touches while moving by motion, items in ignore are skipped
+    fn shape_cast_points(&self, shape: &CastShape, origin: Vec2, motion: Vec2, ignore: &[T]) -> Option<ShapeHit<T>> {
+        let max_dist = motion.length();
+        let dir = motion.normalize_or_zero();
+        let mut hit = self.shape_cast_with(shape, origin, motion, &mut |item, bounds| {
+            if ignore.contains(item) {
+                return None;
+            }
+            sweep_circle(shape, origin, dir, bounds.center(), bounds.half_size.x, max_dist)
+        })?;
+
+        //the contact sits on the circle, not on its bounds
+        let Some(bounds) = self.get(&hit.item) else {
+            return Some(hit);
+        };
+        let to_shape = (hit.center - bounds.center()).normalize_or_zero();
+        hit.point = bounds.center() + to_shape * bounds.half_size.x;
+        return Some(hit);
+    }
+
+    //every pair whose bounds, each grown by its own margin, touch, reported once as (lower, higher) and sorted
+    fn pairs(&self, margin: &dyn Fn(&T) -> f32) -> Vec<(T, T)>
+    where
+        T: Ord,
+    {
+        let elements = self.elements();
+        let margins: HashMap<T, f32> = elements.iter().map(|(item, _)| (*item, margin(item).max(0.))).collect();
+        let max_margin = margins.values().fold(0., |a: f32, b| a.max(*b));
+
+        let mut pairs = Vec::new();
+        for (a, bounds) in elements.iter() {
+            let grown = bounds.grow(margins[a]);
+            //wide enough to catch any neighbour whatever its margin, then checked exactly
+            for (b, b_bounds) in self.query_area(&bounds.grow(margins[a] + max_margin)).iter() {
+                if a < b && grown.intersects(&b_bounds.grow(margins[b])) {
+                    pairs.push((*a, *b));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        return pairs;
+    }
+
+    //cells or nodes worth drawing when debugging
+    fn debug_rects(&self) -> Vec<Rect> {
+        Vec::new()
+    }
+
+    //overlaps that started or ended since the last call, only backends that keep pairs between steps have any
+    fn drain_pair_events(&mut self) -> Vec<PairEvent<T>> {
+        Vec::new()
+    }
+}
+
+impl<T: Copy + Eq + Hash> Broadphase<T> for QuadTree<T> {
+    fn insert(&mut self, item: &T, rect: &Rect) -> bool {
+        QuadTree::insert(self, item, rect)
+    }
+
+    fn update(&mut self, item: &T, rect: &Rect) -> bool {
+        QuadTree::update(self, item, rect)
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        QuadTree::remove(self, item)
+    }
+
+    fn clear(&mut self) {
+        QuadTree::clear(self)
+    }
+
+    fn get(&self, item: &T) -> Option<Rect> {
+        self.tracked.get(item).copied()
+    }
+
+    fn elements(&self) -> Vec<Element<T>> {
+        self.tracked.iter().map(|(item, rect)| (*item, *rect)).collect()
+    }
+
+    fn query_area(&self, rect: &Rect) -> Vec<Element<T>> {
+        QuadTree::query_area(self, rect)
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32) -> Vec<Element<T>> {
+        QuadTree::query_radius(self, center, radius)
+    }
+
+    fn raycast_with(&self, origin: Vec2, dir: Vec2, max_dist: f32, hit_test: HitTest<T>) -> Option<RayHit<T>> {
+        QuadTree::raycast_with(self, origin, dir, max_dist, hit_test)
+    }
+
+    fn debug_rects(&self) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        let mut stack = vec![ROOT];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            match node.children() {
+                Some(children) => stack.extend(children),
+                None => rects.push(node.bounds),
+            }
+        }
+        return rects;
+    }
+}
+
+/*
+    -------------------------------------
+        RESOURCES
+    -------------------------------------
+*/
+
+//which structure indexes the points, changing it rebuilds EntityBroadphase
+//left out, it is filled in from the EntityBroadphase the app inserted
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub enum BroadphaseKind {
+    QuadTree(TreeSettings),
+    //best when every point has about the same radius, cells around twice that
+    HashGrid { cell_size: f32 },
+    //for scenes mixing huge and tiny bodies, the margin trades query precision for fewer tree updates
+    AabbTree { margin: f32 },
+    //cheapest when bodies move a little each step, also the one that reports PairEvents
+    SweepAndPrune,
+}
+
+impl Default for BroadphaseKind {
+    fn default() -> Self {
+        BroadphaseKind::QuadTree(TreeSettings::default())
+    }
+}
+
+//candidate pairs for this step, unique and sorted so contacts are solved in the same order every run
+#[derive(Resource, Default)]
+pub struct BroadphasePairs(pub Vec<(Entity, Entity)>);
+
+//the world's spatial index, keyed by entity
+#[derive(Resource, Deref, DerefMut)]
+pub struct EntityBroadphase {
+    //the playable area, points are kept inside it
+    pub bounds: Rect,
+    kind: BroadphaseKind,
+    #[deref]
+    index: Box<dyn Broadphase<Entity> + Send + Sync>,
+    //stored as a collider's bounds rather than a point's circle, casts hit the box itself
+    colliders: HashSet<Entity>,
+}
+
+impl EntityBroadphase {
+    pub fn new(bounds: Rect, kind: BroadphaseKind) -> Self {
+        Self {
+            bounds: bounds,
+            kind: kind,
+            index: Self::build(&bounds, kind),
+            colliders: HashSet::new(),
+        }
+    }
+
+    fn build(bounds: &Rect, kind: BroadphaseKind) -> Box<dyn Broadphase<Entity> + Send + Sync> {
+        match kind {
+            BroadphaseKind::QuadTree(settings) => Box::new(QuadTree::with_settings(bounds.pos, bounds.size, settings)),
+            BroadphaseKind::HashGrid { cell_size } => Box::new(SpatialHashGrid::new(cell_size)),
+            BroadphaseKind::AabbTree { margin } => Box::new(DynamicAabbTree::new(margin)),
+            BroadphaseKind::SweepAndPrune => Box::new(SweepAndPrune::new()),
+        }
+    }
+
+    pub fn kind(&self) -> BroadphaseKind {
+        self.kind
+    }
+
+    //moves everything tracked over to a new backend
+    pub fn rebuild(&mut self, kind: BroadphaseKind) {
+        let elements = self.index.elements();
+        self.index = Self::build(&self.bounds, kind);
+        self.kind = kind;
+        for (ent, rect) in elements.iter() {
+            self.index.insert(ent, rect);
+        }
+    }
+
+    //a point's bounds, cast against as the circle they were built from
+    pub fn insert(&mut self, ent: &Entity, rect: &Rect) -> bool {
+        self.colliders.remove(ent);
+        self.index.insert(ent, rect)
+    }
+
+    pub fn update(&mut self, ent: &Entity, rect: &Rect) -> bool {
+        self.colliders.remove(ent);
+        self.index.update(ent, rect)
+    }
+
+    //a collider's bounds, which can be any shape so casts take the box as is
+    pub fn update_collider(&mut self, ent: &Entity, rect: &Rect) -> bool {
+        self.colliders.insert(*ent);
+        self.index.update(ent, rect)
+    }
+
+    pub fn remove(&mut self, ent: &Entity) -> bool {
+        self.colliders.remove(ent);
+        self.index.remove(ent)
+    }
+
+    pub fn clear(&mut self) {
+        self.colliders.clear();
+        self.index.clear();
+    }
+
+    //every point circle or collider bounds within radius of center, each reported once
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<Element<Entity>> {
+        let radius_sq = radius * radius;
+        let mut elements = self.index.query_area(&Rect::from_center(center, Vec2::splat(radius)));
+        elements.retain(|(ent, bounds)| {
+            if self.colliders.contains(ent) {
+                return bounds.distance_squared_to(center) <= radius_sq;
+            }
+            bounds.circle_distance_to(center) <= radius
+        });
+        return elements;
+    }
+
+    //first point circle or collider bounds hit along the ray
+    pub fn raycast_points(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<RayHit<Entity>> {
+        let dir = dir.normalize_or_zero();
+        self.index.raycast_with(origin, dir, max_dist, &mut |ent, bounds| {
+            if self.colliders.contains(ent) {
+                return ray_aabb(origin, dir, bounds, max_dist);
+            }
+            ray_circle(origin, dir, bounds.center(), bounds.half_size.x, max_dist)
+        })
+    }
+
+    //earliest point circle or collider bounds the shape touches while moving by motion, entities in ignore are skipped
+    pub fn shape_cast_points(&self, shape: &CastShape, origin: Vec2, motion: Vec2, ignore: &[Entity]) -> Option<ShapeHit<Entity>> {
+        let max_dist = motion.length();
+        let dir = motion.normalize_or_zero();
+        let mut hit = self.index.shape_cast_with(shape, origin, motion, &mut |ent, bounds| {
+            if ignore.contains(ent) {
+                return None;
+            }
+            if self.colliders.contains(ent) {
+                return sweep_aabb(shape, origin, dir, bounds, max_dist);
+            }
+            sweep_circle(shape, origin, dir, bounds.center(), bounds.half_size.x, max_dist)
+        })?;
+
+        //a point's contact sits on its circle, not on its bounds
+        if self.colliders.contains(&hit.item) {
+            return Some(hit);
+        }
+        let Some(bounds) = self.index.get(&hit.item) else {
+            return Some(hit);
+        };
+        let to_shape = (hit.center - bounds.center()).normalize_or_zero();
+        hit.point = bounds.center() + to_shape * bounds.half_size.x;
+        return Some(hit);
+    }
+}
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+pub fn switch_broadphase(
+    mut commands: Commands,
+    kind: Option<Res<BroadphaseKind>>,
+    broadphase: Option<ResMut<EntityBroadphase>>,
+) {
+    let Some(mut broadphase) = broadphase else {
+        return;
+    };
+    match kind {
+        //start from whatever the app built the broadphase as
+        None => commands.insert_resource(broadphase.kind()),
+        Some(kind) => {
+            if kind.is_changed() && broadphase.kind() != *kind {
+                broadphase.rebuild(*kind);
+            }
+        }
+    }
+}
+
+//forwards the overlaps the broadphase started or stopped reporting this step
+pub fn send_pair_events(
+    mut broadphase: ResMut<EntityBroadphase>,
+    mut pair_events: EventWriter<PairEvent<Entity>>,
+) {
+    pair_events.send_batch(broadphase.drain_pair_events());
+}
+
+pub fn draw_broadphase(
+    broadphase: Res<EntityBroadphase>,
+    mut gizmos: Gizmos
+) {
+    for rect in broadphase.debug_rects() {
+        gizmos.rect_2d(rect.center(), 0., rect.size, Color::WHITE);
+    }
+}
+
+//unique items, for backends that store an element in several places
+pub(crate) fn dedup_elements<T: Copy + Eq + Hash>(elements: &mut Vec<Element<T>>) {
+    let mut seen: HashSet<T> = HashSet::new();
+    elements.retain(|(item, _)| seen.insert(*item));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> EntityBroadphase {
+        EntityBroadphase::new(Rect::new(Vec2::new(0., 0.), Vec2::new(1000., 800.)), BroadphaseKind::HashGrid { cell_size: 50. })
+    }
+
+    #[test]
+    fn casts_hit_collider_bounds_as_boxes() {
+        let mut broadphase = world();
+        let (flat, thin) = (Entity::from_raw(1), Entity::from_raw(2));
+        //a wide flat box and a tall thin one, both far from circular
+        broadphase.update_collider(&flat, &Rect::from_center(Vec2::new(300., -300.), Vec2::new(100., 5.)));
+        broadphase.update_collider(&thin, &Rect::from_center(Vec2::new(600., -300.), Vec2::new(5., 100.)));
+
+        //grazing the flat box's corner, a circle of its half width would sit far above
+        let hit = broadphase.raycast_points(Vec2::new(395., -200.), Vec2::NEG_Y, 200.).unwrap();
+        assert_eq!(hit.item, flat);
+        assert!((hit.distance - 95.).abs() < 1e-3);
+
+        //the thin box reaches well past a circle of its half width
+        let hit = broadphase.shape_cast_points(&CastShape::Circle(2.), Vec2::new(500., -390.), Vec2::new(200., 0.), &[]).unwrap();
+        assert_eq!(hit.item, thin);
+        assert!((hit.center.x - 593.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn radius_queries_follow_the_stored_shape() {
+        let mut broadphase = world();
+        let (flat, point) = (Entity::from_raw(1), Entity::from_raw(2));
+        broadphase.update_collider(&flat, &Rect::from_center(Vec2::new(300., -300.), Vec2::new(100., 5.)));
+        broadphase.insert(&point, &Rect::from_center(Vec2::new(600., -300.), Vec2::splat(10.)));
+
+        //by the flat box's far end, nowhere near a circle of its half width
+        let found: Vec<Entity> = broadphase.query_radius(Vec2::new(405., -300.), 6.).iter().map(|(ent, _)| *ent).collect();
+        assert_eq!(found, vec![flat]);
+        //inside the point's bounds corner, outside its circle
+        assert!(broadphase.query_radius(Vec2::new(609., -291.), 2.).is_empty());
+    }
+
+    #[test]
+    fn casts_hit_points_as_circles() {
+        let mut broadphase = world();
+        let point = Entity::from_raw(1);
+        broadphase.insert(&point, &Rect::from_center(Vec2::new(300., -300.), Vec2::splat(10.)));
+
+        //across the corner of its bounds, passing the circle at 19 / sqrt 2 from its center
+        let corner = Vec2::new(309.5, -290.5);
+        let dir = Vec2::new(1., -1.).normalize();
+        let across = |broadphase: &EntityBroadphase| broadphase.raycast_points(corner - dir * 50., dir, 100.);
+        assert!(across(&broadphase).is_none());
+        let hit = broadphase.raycast_points(Vec2::new(309., -200.), Vec2::NEG_Y, 200.).unwrap();
+        assert!((hit.distance - (100. - 19f32.sqrt())).abs() < 1e-3);
+
+        //a point that was a collider goes back to a circle once updated as a point
+        broadphase.update_collider(&point, &Rect::from_center(Vec2::new(300., -300.), Vec2::splat(10.)));
+        assert!(across(&broadphase).is_some());
+        broadphase.update(&point, &Rect::from_center(Vec2::new(300., -300.), Vec2::splat(10.)));
+        assert!(across(&broadphase).is_none());
+    }
+}
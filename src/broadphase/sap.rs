@@ -0,0 +1,424 @@
+#![allow(clippy::needless_return)]
+
+use std::hash::Hash;
+
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+
+use crate::quadtree::{Element, Rect};
+use super::Broadphase;
+
+/*
+    ---
+    SOURCES
+    https://github.com/mattleibow/jitterphysics/wiki/Sweep-and-Prune
+    http://www.codercorner.com/SAP.pdf
+    ---
+*/
+
+//overlaps the broadphase started or stopped reporting since the last drain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PairEvent<T> {
+    Added(T, T),
+    Removed(T, T),
+}
+
+impl<T: Send + Sync + 'static> Event for PairEvent<T> {}
+
+#[derive(Debug, Clone, Copy)]
+struct Endpoint {
+    value: f32,
+    proxy: usize,
+    is_min: bool,
+}
+
+impl Endpoint {
+    //at equal values mins go first, so touching bounds count as overlapping like Rect::intersects
+    fn after(&self, other: &Endpoint) -> bool {
+        self.value > other.value || (self.value == other.value && !self.is_min && other.is_min)
+    }
+}
+
+#[derive(Debug)]
+struct Proxy<T> {
+    item: T,
+    bounds: Rect,
+    //where the proxy's endpoints sit in each axis list, x then y
+    min_index: [usize; 2],
+    max_index: [usize; 2],
+}
+
+//sorted endpoint lists on both axes, kept sorted between steps by insertion sort,
+//the swaps that sort them are exactly the overlaps that start or end
+#[derive(Debug)]
+pub struct SweepAndPrune<T> {
+    axes: [Vec<Endpoint>; 2],
+    proxies: Vec<Option<Proxy<T>>>,
+    free: Vec<usize>,
+    ids: HashMap<T, usize>,
+    //overlapping proxies, lower slot first
+    pairs: HashSet<(usize, usize)>,
+    events: Vec<PairEvent<T>>,
+}
+
+impl<T: Copy + Eq + Hash> Default for SweepAndPrune<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + Eq + Hash> SweepAndPrune<T> {
+    pub fn new() -> Self {
+        Self {
+            axes: [Vec::new(), Vec::new()],
+            proxies: Vec::new(),
+            free: Vec::new(),
+            ids: HashMap::new(),
+            pairs: HashSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    //every pair whose bounds currently overlap
+    pub fn pairs(&self) -> impl Iterator<Item = (T, T)> + '_ {
+        self.pairs.iter().map(|(a, b)| (self.proxy(*a).item, self.proxy(*b).item))
+    }
+
+    pub fn drain_events(&mut self) -> Vec<PairEvent<T>> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn proxy(&self, index: usize) -> &Proxy<T> {
+        self.proxies[index].as_ref().unwrap()
+    }
+
+    fn proxy_mut(&mut self, index: usize) -> &mut Proxy<T> {
+        self.proxies[index].as_mut().unwrap()
+    }
+
+    fn extents(rect: &Rect, axis: usize) -> (f32, f32) {
+        match axis {
+            0 => (rect.left, rect.right),
+            _ => (rect.bottom, rect.top),
+        }
+    }
+
+    fn set_index(&mut self, axis: usize, position: usize) {
+        let endpoint = self.axes[axis][position];
+        let proxy = self.proxy_mut(endpoint.proxy);
+        if endpoint.is_min {
+            proxy.min_index[axis] = position;
+        } else {
+            proxy.max_index[axis] = position;
+        }
+    }
+
+    fn add_pair(&mut self, a: usize, b: usize) {
+        let key = (a.min(b), a.max(b));
+        if self.pairs.insert(key) {
+            self.events.push(PairEvent::Added(self.proxy(key.0).item, self.proxy(key.1).item));
+        }
+    }
+
+    fn remove_pair(&mut self, a: usize, b: usize) {
+        let key = (a.min(b), a.max(b));
+        if self.pairs.remove(&key) {
+            self.events.push(PairEvent::Removed(self.proxy(key.0).item, self.proxy(key.1).item));
+        }
+    }
+
+    //right passes left, a min passing a max can start an overlap and a max passing a min ends one
+    fn swap(&mut self, axis: usize, left: usize, right: usize) {
+        let l = self.axes[axis][left];
+        let r = self.axes[axis][right];
+        if l.proxy != r.proxy {
+            if r.is_min && !l.is_min {
+                if self.proxy(l.proxy).bounds.intersects(&self.proxy(r.proxy).bounds) {
+                    self.add_pair(l.proxy, r.proxy);
+                }
+            } else if !r.is_min && l.is_min {
+                self.remove_pair(l.proxy, r.proxy);
+            }
+        }
+
+        self.axes[axis].swap(left, right);
+        self.set_index(axis, left);
+        self.set_index(axis, right);
+    }
+
+    fn sort_endpoint(&mut self, axis: usize, mut position: usize) {
+        while position > 0 && self.axes[axis][position - 1].after(&self.axes[axis][position]) {
+            self.swap(axis, position - 1, position);
+            position -= 1;
+        }
+        while position + 1 < self.axes[axis].len() && self.axes[axis][position].after(&self.axes[axis][position + 1]) {
+            self.swap(axis, position, position + 1);
+            position += 1;
+        }
+    }
+
+    //writes the new bounds into the endpoints and sorts them back into place
+    fn move_proxy(&mut self, index: usize, rect: &Rect) {
+        self.proxy_mut(index).bounds = *rect;
+
+        for axis in 0..2 {
+            let (min, max) = Self::extents(rect, axis);
+            let min_index = self.proxy(index).min_index[axis];
+            let max_index = self.proxy(index).max_index[axis];
+            let moving_right = min > self.axes[axis][min_index].value;
+            self.axes[axis][min_index].value = min;
+            self.axes[axis][max_index].value = max;
+
+            //the leading endpoint goes first so the two never cross each other
+            if moving_right {
+                self.sort_endpoint(axis, max_index);
+                let min_index = self.proxy(index).min_index[axis];
+                self.sort_endpoint(axis, min_index);
+            } else {
+                self.sort_endpoint(axis, min_index);
+                let max_index = self.proxy(index).max_index[axis];
+                self.sort_endpoint(axis, max_index);
+            }
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Broadphase<T> for SweepAndPrune<T> {
+    fn insert(&mut self, item: &T, rect: &Rect) -> bool {
+        if self.ids.contains_key(item) {
+            return self.update(item, rect);
+        }
+
+        let proxy = Proxy { item: *item, bounds: *rect, min_index: [0; 2], max_index: [0; 2] };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.proxies[index] = Some(proxy);
+                index
+            }
+            None => {
+                self.proxies.push(Some(proxy));
+                self.proxies.len() - 1
+            }
+        };
+        self.ids.insert(*item, index);
+
+        //new endpoints start past everything and sort in from the end
+        for axis in 0..2 {
+            let len = self.axes[axis].len();
+            self.axes[axis].push(Endpoint { value: f32::MAX, proxy: index, is_min: true });
+            self.axes[axis].push(Endpoint { value: f32::MAX, proxy: index, is_min: false });
+            let proxy = self.proxy_mut(index);
+            proxy.min_index[axis] = len;
+            proxy.max_index[axis] = len + 1;
+        }
+        self.move_proxy(index, rect);
+        return true;
+    }
+
+    fn update(&mut self, item: &T, rect: &Rect) -> bool {
+        let Some(index) = self.ids.get(item).copied() else {
+            return self.insert(item, rect);
+        };
+        if self.proxy(index).bounds != *rect {
+            self.move_proxy(index, rect);
+        }
+        return true;
+    }
+
+    fn remove(&mut self, item: &T) -> bool {
+        let Some(index) = self.ids.remove(item) else {
+            return false;
+        };
+
+        //sorting the endpoints out past everything ends each of its overlaps along the way
+        let far = Rect::from_center(Vec2::splat(f32::MAX / 4.), Vec2::ZERO);
+        self.move_proxy(index, &far);
+        for axis in 0..2 {
+            self.axes[axis].truncate(self.axes[axis].len() - 2);
+        }
+
+        let stale: Vec<(usize, usize)> = self.pairs.iter().filter(|(a, b)| *a == index || *b == index).copied().collect();
+        for (a, b) in stale {
+            self.remove_pair(a, b);
+        }
+
+        self.proxies[index] = None;
+        self.free.push(index);
+        return true;
+    }
+
+    fn clear(&mut self) {
+        for axis in self.axes.iter_mut() {
+            axis.clear();
+        }
+        self.proxies.clear();
+        self.free.clear();
+        self.ids.clear();
+        self.pairs.clear();
+        self.events.clear();
+    }
+
+    fn get(&self, item: &T) -> Option<Rect> {
+        self.ids.get(item).map(|index| self.proxy(*index).bounds)
+    }
+
+    fn elements(&self) -> Vec<Element<T>> {
+        self.proxies.iter().flatten().map(|proxy| (proxy.item, proxy.bounds)).collect()
+    }
+
+    //walks the x list up to the right edge of rect, each proxy is visited once through its min endpoint
+    fn query_area(&self, rect: &Rect) -> Vec<Element<T>> {
+        let end = self.axes[0].partition_point(|endpoint| endpoint.value <= rect.right);
+        self.axes[0][..end].iter()
+            .filter(|endpoint| endpoint.is_min)
+            .map(|endpoint| self.proxy(endpoint.proxy))
+            .filter(|proxy| proxy.bounds.intersects(rect))
+            .map(|proxy| (proxy.item, proxy.bounds))
+            .collect()
+    }
+
+    //the x list is already sorted, so one sweep over it finds every pair
+    fn pairs(&self, margin: &dyn Fn(&T) -> f32) -> Vec<(T, T)>
+    where
+        T: Ord,
+    {
+        let margins: Vec<f32> = self.proxies.iter()
+            .map(|proxy| proxy.as_ref().map_or(0., |proxy| margin(&proxy.item).max(0.)))
+            .collect();
+        let max_margin = margins.iter().fold(0., |a: f32, b| a.max(*b));
+
+        let mut pairs = Vec::new();
+        let mut active: Vec<usize> = Vec::new();
+        for endpoint in self.axes[0].iter().filter(|endpoint| endpoint.is_min) {
+            //ends further left than any margin can bridge, so nothing from here on reaches it
+            active.retain(|a| self.proxy(*a).bounds.right + margins[*a] + max_margin >= endpoint.value);
+
+            let b = self.proxy(endpoint.proxy);
+            let grown = b.bounds.grow(margins[endpoint.proxy]);
+            for a in active.iter() {
+                let proxy = self.proxy(*a);
+                if proxy.bounds.grow(margins[*a]).intersects(&grown) {
+                    pairs.push((proxy.item.min(b.item), proxy.item.max(b.item)));
+                }
+            }
+            active.push(endpoint.proxy);
+        }
+        pairs.sort_unstable();
+        return pairs;
+    }
+
+    fn drain_pair_events(&mut self) -> Vec<PairEvent<T>> {
+        SweepAndPrune::drain_events(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn key(a: u32, b: u32) -> (u32, u32) {
+        (a.min(b), a.max(b))
+    }
+
+    fn brute_force_pairs(sap: &SweepAndPrune<u32>) -> HashSet<(u32, u32)> {
+        let elements = sap.elements();
+        let mut pairs = HashSet::new();
+        for (i, (a, bounds_a)) in elements.iter().enumerate() {
+            for (b, bounds_b) in elements.iter().skip(i + 1) {
+                if bounds_a.intersects(bounds_b) {
+                    pairs.insert(key(*a, *b));
+                }
+            }
+        }
+        return pairs;
+    }
+
+    //slides b past a along axis, a sits at the origin and both are 10 wide
+    fn slide_past(axis: Vec2, offset: Vec2) -> Vec<PairEvent<u32>> {
+        let mut sap = SweepAndPrune::new();
+        sap.insert(&0, &Rect::from_center(Vec2::ZERO, Vec2::splat(5.)));
+        sap.insert(&1, &Rect::from_center(offset - axis * 40., Vec2::splat(5.)));
+        sap.drain_events();
+
+        let mut events = Vec::new();
+        for step in 0..=80 {
+            let center = offset + axis * (step as f32 - 40.);
+            sap.update(&1, &Rect::from_center(center, Vec2::splat(5.)));
+            events.extend(sap.drain_events());
+            assert_eq!(sap.pairs().map(|(a, b)| key(a, b)).collect::<HashSet<_>>(), brute_force_pairs(&sap));
+        }
+        return events;
+    }
+
+    #[test]
+    fn passing_through_starts_and_stops_once() {
+        for axis in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+            let events = slide_past(axis, Vec2::ZERO);
+            assert_eq!(events, vec![PairEvent::Added(0, 1), PairEvent::Removed(0, 1)], "moving along {axis}");
+        }
+    }
+
+    #[test]
+    fn passing_by_on_another_row_stays_quiet() {
+        //overlapping on the axis of motion only, the other axis keeps them apart
+        assert!(slide_past(Vec2::X, Vec2::new(0., 20.)).is_empty());
+        assert!(slide_past(Vec2::Y, Vec2::new(20., 0.)).is_empty());
+        //just touching edges counts as overlapping, like Rect::intersects
+        assert_eq!(slide_past(Vec2::X, Vec2::new(0., 10.)), vec![PairEvent::Added(0, 1), PairEvent::Removed(0, 1)]);
+    }
+
+    #[test]
+    fn removing_a_proxy_stops_its_pairs() {
+        let mut sap = SweepAndPrune::new();
+        sap.insert(&0, &Rect::from_center(Vec2::ZERO, Vec2::splat(5.)));
+        sap.insert(&1, &Rect::from_center(Vec2::new(4., 0.), Vec2::splat(5.)));
+        sap.insert(&2, &Rect::from_center(Vec2::new(-4., 0.), Vec2::splat(5.)));
+        sap.drain_events();
+
+        sap.remove(&0);
+        let mut removed: Vec<(u32, u32)> = sap.drain_events().iter()
+            .map(|event| match event {
+                PairEvent::Removed(a, b) => key(*a, *b),
+                PairEvent::Added(a, b) => panic!("unexpected start of ({a}, {b})"),
+            })
+            .collect();
+        removed.sort_unstable();
+        assert_eq!(removed, vec![(0, 1), (0, 2)]);
+        assert_eq!(sap.pairs().map(|(a, b)| key(a, b)).collect::<Vec<_>>(), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn events_track_brute_force_through_churn() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut sap = SweepAndPrune::new();
+        //what replaying every event so far says is overlapping
+        let mut replayed: HashSet<(u32, u32)> = HashSet::new();
+
+        for step in 0..400 {
+            for _ in 0..10 {
+                let item = rng.gen_range(0..60);
+                if rng.gen_bool(0.1) {
+                    sap.remove(&item);
+                    continue;
+                }
+                let center = match sap.get(&item) {
+                    //mostly short hops so proxies cross each other's endpoints one at a time
+                    Some(bounds) if rng.gen_bool(0.8) => bounds.center() + Vec2::new(rng.gen_range(-8.0..8.), rng.gen_range(-8.0..8.)),
+                    _ => Vec2::new(rng.gen_range(0.0..300.), rng.gen_range(-300.0..0.)),
+                };
+                sap.update(&item, &Rect::from_center(center, Vec2::new(rng.gen_range(2.0..15.), rng.gen_range(2.0..15.))));
+            }
+
+            for event in sap.drain_events() {
+                match event {
+                    PairEvent::Added(a, b) => assert!(replayed.insert(key(a, b)), "step {step}: ({a}, {b}) started twice"),
+                    PairEvent::Removed(a, b) => assert!(replayed.remove(&key(a, b)), "step {step}: ({a}, {b}) stopped without starting"),
+                }
+            }
+            let expected = brute_force_pairs(&sap);
+            assert_eq!(replayed, expected, "step {step}");
+            assert_eq!(sap.pairs().map(|(a, b)| key(a, b)).collect::<HashSet<_>>(), expected, "step {step}");
+        }
+    }
+}
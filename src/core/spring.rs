@@ -1,8 +1,13 @@
 #![allow(unused)]
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::f32::consts::{PI, TAU};
 
 use bevy::prelude::*;
 
 const EPSILON: f32 = 0.0001;
+const SETTLE_DISTANCE: f32 = 0.001;
+const SETTLE_SPEED: f32 = 0.001;
 
 /*
     ---
@@ -16,198 +21,383 @@ pub struct SpringPlugin;
 impl Plugin for SpringPlugin {
     fn build(&self, app: &mut App) {
         app
-            .register_type::<Spring>()
-            .register_type::<SpringVec>()
-            .add_systems(PostUpdate, update_springs);
-    }   
+            .register_type::<Spring<f32>>()
+            .register_type::<Spring<Vec2>>()
+            .register_type::<Spring<Vec3>>()
+            .register_type::<Spring<Vec4>>()
+            .register_type::<Spring<Color>>()
+            .register_type::<AngleSpring>()
+            .register_type::<RotationSpring>()
+            .register_type::<ScaleSpring>()
+            .add_event::<SpringSettled>()
+            .add_systems(PostUpdate, (
+                update_springs::<f32>,
+                (update_springs::<Vec2>, drive_translation).chain(),
+                update_springs::<Vec3>,
+                update_springs::<Vec4>,
+                update_springs::<Color>,
+                drive_transform::<AngleSpring>,
+                drive_transform::<RotationSpring>,
+                drive_transform::<ScaleSpring>,
+            ));
+    }
 }
 
-fn update_springs(
-    mut spring_query: Query<&mut Spring, Without<SpringUpdateIgnore>>,
-    mut spring_vec_query: Query<(&mut SpringVec, &mut Transform), (Without<SpringUpdateIgnore>, Without<Spring>)>,
+fn update_springs<T: SpringValue>(
+    mut spring_query: Query<(Entity, &mut Spring<T>), Without<SpringUpdateIgnore>>,
+    mut settled_events: EventWriter<SpringSettled>,
     dt: Res<Time>
 ) {
-    for mut spring in spring_query.iter_mut() {
-        spring.update(dt.delta_seconds());
+    let mut batch = SpringBatch::default();
+    for (entity, mut spring) in spring_query.iter_mut() {
+        //checked before borrowing mutably so idle springs don't trip change detection
+        if spring.is_resting() {
+            continue;
+        }
+        if batch.update(&mut spring, dt.delta_seconds()) {
+            settled_events.send(SpringSettled(entity));
+        }
     }
+}
 
-    for (mut spring, mut transform) in spring_vec_query.iter_mut() {
-        spring.update(dt.delta_seconds());
-        transform.translation = spring.position.extend(transform.translation.z);
+//steps many springs at once, springs sharing (angular frequency, damping ratio) share one coefficient computation
+pub fn update_batch<'a, T: SpringValue>(
+    springs: impl IntoIterator<Item = &'a mut Spring<T>>,
+    delta_time: f32
+) {
+    let mut batch = SpringBatch::default();
+    for spring in springs {
+        batch.update(spring, delta_time);
     }
 }
 
-#[derive(Component)]
-pub struct SpringUpdateIgnore;
+//remembers the last coefficients it computed so the next spring with the same settings and dt can reuse them
+#[derive(Default)]
+pub struct SpringBatch {
+    shared: Option<((SpringKey, f32), SpringCoefficients)>,
+}
 
-#[derive(Component, Reflect)]
-pub struct Spring {
-    pub angular_frequency: f32, 
-    pub damping_ratio: f32,
+impl SpringBatch {
+    //same as Spring::update, returns true when the spring came to rest
+    pub fn update<T: SpringValue>(&mut self, spring: &mut Spring<T>, delta_time: f32) -> bool {
+        let key = (spring.key(), delta_time);
+        if spring.coefs_key != Some(key) {
+            match self.shared {
+                Some((shared_key, coefs)) if shared_key == key => {
+                    spring.coefs = coefs;
+                    spring.coefs_key = Some(key);
+                }
+                _ => {
+                    spring.refresh_coefficients(delta_time);
+                    self.shared = Some((key, spring.coefs));
+                }
+            }
+        }
+        spring.step()
+    }
+}
 
-    pub target: f32,
-    pub position: f32,
-    pub velocity: f32,
+fn drive_translation(
+    mut spring_query: Query<(Ref<Spring<Vec2>>, &mut Transform), Without<SpringUpdateIgnore>>,
+) {
+    for (spring, mut transform) in spring_query.iter_mut() {
+        //resting springs aren't touched by update_springs, leaving their Transform alone keeps Changed<Transform> quiet
+        if !spring.is_changed() {
+            continue;
+        }
+        transform.translation = spring.position.extend(transform.translation.z);
+    }
+}
 
-    pos_coef: f32,
-    pos_vel_coef: f32,
-    vel_pos_coef: f32,
-    vel_coef: f32
+fn drive_transform<S: TransformSpring>(
+    mut spring_query: Query<(Entity, &mut S, &mut Transform), Without<SpringUpdateIgnore>>,
+    mut settled_events: EventWriter<SpringSettled>,
+    dt: Res<Time>
+) {
+    for (entity, mut spring, mut transform) in spring_query.iter_mut() {
+        if spring.is_resting() {
+            //new springs start at rest on their target, they still have to put the transform there once
+            if spring.is_added() {
+                spring.apply(&mut transform);
+            }
+            continue;
+        }
+        if spring.update(dt.delta_seconds()) {
+            settled_events.send(SpringSettled(entity));
+        }
+        spring.apply(&mut transform);
+    }
 }
 
-impl Spring {
-    pub fn new(target: f32, angular_freq: f32, damping_ratio: f32) -> Spring {
-        let mut new_spring = Spring {
-            angular_frequency: 0.,
-            damping_ratio: 0.,
+#[derive(Component)]
+pub struct SpringUpdateIgnore;
 
-            target: target,
-            position: 0.,
-            velocity: 0.,
+//sent once when a spring reaches its target, idle springs are skipped until they are moved again
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SpringSettled(pub Entity);
 
-            pos_coef: 0.0,
-            pos_vel_coef: 0.0,
-            vel_pos_coef: 0.0,
-            vel_coef: 0.0,
-        };
+/*
+    -------------------------------------
+        SPRING VALUES
+    -------------------------------------
+*/
 
-        new_spring.set_angular(angular_freq);
-        new_spring.set_damping(damping_ratio);
+//anything a spring can move, it only has to behave like a vector space
+pub trait SpringValue: Copy + Send + Sync + 'static {
+    fn zero() -> Self;
+    fn add(self, rhs: Self) -> Self;
+    fn sub(self, rhs: Self) -> Self;
+    fn scale(self, s: f32) -> Self;
+    fn length(self) -> f32;
+}
 
-        return new_spring;
-    }
+macro_rules! impl_spring_value {
+    ($($ty:ty = $zero:expr, $length:path);*) => {
+        $(
+            impl SpringValue for $ty {
+                fn zero() -> Self {
+                    $zero
+                }
+
+                fn length(self) -> f32 {
+                    $length(self)
+                }
+
+                fn add(self, rhs: Self) -> Self {
+                    self + rhs
+                }
+
+                fn sub(self, rhs: Self) -> Self {
+                    self - rhs
+                }
+
+                fn scale(self, s: f32) -> Self {
+                    self * s
+                }
+            }
+        )*
+    };
+}
 
-    pub fn set_damping(&mut self, ratio: f32) {
-        self.damping_ratio = if ratio < 0.0 {
-            0.0
-        } else {
-            ratio
-        };
-    }
-    
-    pub fn set_angular(&mut self, angle: f32) {
-        self.angular_frequency = if angle < 0.0 {
-            0.0
-        } else {
-            angle
-        };
+impl_spring_value!(
+    f32 = 0., f32::abs;
+    Vec2 = Vec2::ZERO, Vec2::length;
+    Vec3 = Vec3::ZERO, Vec3::length;
+    Vec4 = Vec4::ZERO, Vec4::length
+);
+
+//colors are sprung in linear rgba so blends don't darken halfway
+impl SpringValue for Color {
+    fn zero() -> Self {
+        Color::rgba_linear(0., 0., 0., 0.)
     }
 
-    pub fn shove(
-        &mut self,
-        goal: f32,
-    ) {
-        self.position += goal;
+    fn add(self, rhs: Self) -> Self {
+        linear_color(linear_vec(self) + linear_vec(rhs))
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        if self.angular_frequency < EPSILON {
-            self.pos_coef = 1.0;
-            self.pos_vel_coef = 0.0;
-            self.vel_pos_coef = 0.0;
-            self.vel_coef = 1.0;
-            return;
-        }
-
-        if self.damping_ratio > (1.0 + EPSILON) {
-            //over-damped
-            let za = -self.angular_frequency * self.damping_ratio;
-            let zb = self.angular_frequency * f32::sqrt(self.damping_ratio * self.damping_ratio - 1.0);
-            let z1 = za - zb;
-            let z2 = za + zb;
-
-            let e1 = f32::exp(z1 * delta_time);
-            let e2 = f32::exp(z2 * delta_time);
-
-            let inv_two_zb = 1.0 / (2.0 * zb);
-
-            let e1_over_twozb = e1 * inv_two_zb;
-            let e2_over_twozb = e2 * inv_two_zb;
+    fn sub(self, rhs: Self) -> Self {
+        linear_color(linear_vec(self) - linear_vec(rhs))
+    }
 
-            let z1e1_over_twozb = z1 * e1_over_twozb;
-            let z2e2_over_twozb = z2*e2_over_twozb;
+    fn scale(self, s: f32) -> Self {
+        linear_color(linear_vec(self) * s)
+    }
 
-            self.pos_coef = e1_over_twozb * z2 - z2e2_over_twozb + e2;
-            self.pos_vel_coef = -e1_over_twozb + e2_over_twozb;
+    fn length(self) -> f32 {
+        linear_vec(self).length()
+    }
+}
 
-            self.vel_pos_coef = (z1e1_over_twozb - z2e2_over_twozb + e2) * z2;
-            self.vel_coef = -z1e1_over_twozb + z2e2_over_twozb;
-        } else if self.damping_ratio < (1.0 - EPSILON) {
-            //under-damped
-            let omega_zeta = self.angular_frequency * self.damping_ratio;
-            let alpha = self.angular_frequency * f32::sqrt(1.0 - (self.damping_ratio * self.damping_ratio));
+fn linear_vec(color: Color) -> Vec4 {
+    Vec4::from_array(color.as_linear_rgba_f32())
+}
 
-            let exp_term = f32::exp(-omega_zeta * delta_time);
-            let cos_term = f32::cos(alpha * delta_time);
-            let sin_term = f32::sin(alpha * delta_time);
+fn linear_color(v: Vec4) -> Color {
+    Color::rgba_linear(v.x, v.y, v.z, v.w)
+}
 
-            let inv_alpha = 1.0 / alpha;
+/*
+    -------------------------------------
+        SPRING
+    -------------------------------------
+*/
 
-            let exp_sin = exp_term * sin_term;
-            let exp_cos = exp_term * cos_term;
-            let expomega_zeta_sin_over_alpha = exp_term * omega_zeta * sin_term * inv_alpha;
+//a spring's settings, everything about its response that doesn't depend on the frame's dt is cached on these
+#[derive(Clone, Copy, PartialEq)]
+struct SpringKey {
+    angular_frequency: f32,
+    damping_ratio: f32,
+}
 
-            self.pos_coef = exp_cos + expomega_zeta_sin_over_alpha;
-            self.pos_vel_coef = exp_sin * inv_alpha;
+//the dt independent half of the coefficients, the rest is a few exp / sin / cos per dt
+#[derive(Default, Clone, Copy)]
+enum SpringResponse {
+    #[default]
+    Still,
+    OverDamped {
+        z1: f32,
+        z2: f32,
+        inv_two_zb: f32,
+    },
+    UnderDamped {
+        omega_zeta: f32,
+        alpha: f32,
+        inv_alpha: f32,
+    },
+    CriticallyDamped {
+        angular_frequency: f32,
+    },
+}
 
-            self.vel_pos_coef = -exp_sin * alpha - omega_zeta * expomega_zeta_sin_over_alpha;
-            self.vel_coef = exp_cos - expomega_zeta_sin_over_alpha;
-        } else {
-            //critically damped
-            let exp_term = f32::exp(-self.angular_frequency * delta_time);
-            let time_exp = delta_time * exp_term;
-            let time_exp_freq = time_exp * self.angular_frequency;
+impl SpringResponse {
+    fn new(key: SpringKey) -> Self {
+        let (angular_frequency, damping_ratio) = (key.angular_frequency, key.damping_ratio);
+        if angular_frequency < EPSILON {
+            return SpringResponse::Still;
+        }
 
-            self.pos_coef = time_exp_freq + exp_term;
-            self.pos_vel_coef = time_exp;
+        if damping_ratio > (1.0 + EPSILON) {
+            let za = -angular_frequency * damping_ratio;
+            let zb = angular_frequency * f32::sqrt(damping_ratio * damping_ratio - 1.0);
+            return SpringResponse::OverDamped { z1: za - zb, z2: za + zb, inv_two_zb: 1.0 / (2.0 * zb) };
+        }
+        if damping_ratio < (1.0 - EPSILON) {
+            let alpha = angular_frequency * f32::sqrt(1.0 - (damping_ratio * damping_ratio));
+            return SpringResponse::UnderDamped { omega_zeta: angular_frequency * damping_ratio, alpha: alpha, inv_alpha: 1.0 / alpha };
+        }
+        return SpringResponse::CriticallyDamped { angular_frequency: angular_frequency };
+    }
 
-            self.vel_pos_coef = -self.angular_frequency * time_exp_freq;
-            self.vel_coef = -time_exp_freq + exp_term;
+    fn coefficients(&self, delta_time: f32) -> SpringCoefficients {
+        match *self {
+            SpringResponse::Still => SpringCoefficients::IDENTITY,
+            SpringResponse::OverDamped { z1, z2, inv_two_zb } => {
+                let e1 = f32::exp(z1 * delta_time);
+                let e2 = f32::exp(z2 * delta_time);
+
+                let e1_over_twozb = e1 * inv_two_zb;
+                let e2_over_twozb = e2 * inv_two_zb;
+
+                let z1e1_over_twozb = z1 * e1_over_twozb;
+                let z2e2_over_twozb = z2 * e2_over_twozb;
+
+                SpringCoefficients {
+                    pos_coef: e1_over_twozb * z2 - z2e2_over_twozb + e2,
+                    pos_vel_coef: -e1_over_twozb + e2_over_twozb,
+
+                    vel_pos_coef: (z1e1_over_twozb - z2e2_over_twozb + e2) * z2,
+                    vel_coef: -z1e1_over_twozb + z2e2_over_twozb,
+                }
+            }
+            SpringResponse::UnderDamped { omega_zeta, alpha, inv_alpha } => {
+                let exp_term = f32::exp(-omega_zeta * delta_time);
+                let cos_term = f32::cos(alpha * delta_time);
+                let sin_term = f32::sin(alpha * delta_time);
+
+                let exp_sin = exp_term * sin_term;
+                let exp_cos = exp_term * cos_term;
+                let expomega_zeta_sin_over_alpha = exp_term * omega_zeta * sin_term * inv_alpha;
+
+                SpringCoefficients {
+                    pos_coef: exp_cos + expomega_zeta_sin_over_alpha,
+                    pos_vel_coef: exp_sin * inv_alpha,
+
+                    vel_pos_coef: -exp_sin * alpha - omega_zeta * expomega_zeta_sin_over_alpha,
+                    vel_coef: exp_cos - expomega_zeta_sin_over_alpha,
+                }
+            }
+            SpringResponse::CriticallyDamped { angular_frequency } => {
+                let exp_term = f32::exp(-angular_frequency * delta_time);
+                let time_exp = delta_time * exp_term;
+                let time_exp_freq = time_exp * angular_frequency;
+
+                SpringCoefficients {
+                    pos_coef: time_exp_freq + exp_term,
+                    pos_vel_coef: time_exp,
+
+                    vel_pos_coef: -angular_frequency * time_exp_freq,
+                    vel_coef: -time_exp_freq + exp_term,
+                }
+            }
         }
+    }
+}
 
-        //update spring
-        let old_pos = self.position - self.target;
-        let old_vel = self.velocity;
+#[derive(Default, Clone, Copy)]
+pub struct SpringCoefficients {
+    pos_coef: f32,
+    pos_vel_coef: f32,
+    vel_pos_coef: f32,
+    vel_coef: f32
+}
 
-        self.position = old_pos * self.pos_coef + old_vel * self.pos_vel_coef + self.target;
-        self.velocity = old_pos * self.vel_pos_coef + old_vel * self.vel_coef;
+impl SpringCoefficients {
+    const IDENTITY: SpringCoefficients = SpringCoefficients {
+        pos_coef: 1.0,
+        pos_vel_coef: 0.0,
+        vel_pos_coef: 0.0,
+        vel_coef: 1.0,
+    };
+
+    pub fn new(angular_frequency: f32, damping_ratio: f32, delta_time: f32) -> Self {
+        let key = SpringKey { angular_frequency: angular_frequency, damping_ratio: damping_ratio };
+        SpringResponse::new(key).coefficients(delta_time)
     }
 }
 
-
+//f32 by default so plain `Spring` keeps meaning a scalar spring
 #[derive(Component, Reflect)]
-pub struct SpringVec {
-    pub angular_frequency: f32, 
+pub struct Spring<T: SpringValue = f32> {
+    pub angular_frequency: f32,
     pub damping_ratio: f32,
 
-    pub target: Vec2,
-    pub position: Vec2,
-    pub velocity: Vec2,
-
-    pos_coef: f32,
-    pos_vel_coef: f32,
-    vel_pos_coef: f32,
-    vel_coef: f32
+    pub target: T,
+    pub position: T,
+    pub velocity: T,
+
+    //the spring is settled once it is this close to the target and this slow
+    pub settle_distance: f32,
+    pub settle_speed: f32,
+
+    #[reflect(ignore)]
+    coefs: SpringCoefficients,
+    //what coefs were last computed for
+    #[reflect(ignore)]
+    coefs_key: Option<(SpringKey, f32)>,
+    #[reflect(ignore)]
+    response: SpringResponse,
+    #[reflect(ignore)]
+    response_key: Option<SpringKey>,
+    #[reflect(ignore)]
+    resting: bool,
 }
 
-impl SpringVec {
-    pub fn new(target: Vec2, angular_freq: f32, damping_ratio: f32) -> SpringVec {
-        let mut new_spring = SpringVec {
+pub type SpringVec = Spring<Vec2>;
+
+impl<T: SpringValue> Spring<T> {
+    pub fn new(target: T, angular_freq: f32, damping_ratio: f32) -> Spring<T> {
+        let mut new_spring = Spring {
             angular_frequency: 0.,
             damping_ratio: 0.,
 
             target: target,
-            position: Vec2::ZERO,
-            velocity: Vec2::ZERO,
+            position: T::zero(),
+            velocity: T::zero(),
+
+            settle_distance: SETTLE_DISTANCE,
+            settle_speed: SETTLE_SPEED,
 
-            pos_coef: 0.0,
-            pos_vel_coef: 0.0,
-            vel_pos_coef: 0.0,
-            vel_coef: 0.0,
+            coefs: SpringCoefficients::default(),
+            coefs_key: None,
+            response: SpringResponse::default(),
+            response_key: None,
+            resting: false,
         };
 
         new_spring.set_angular(angular_freq);
         new_spring.set_damping(damping_ratio);
+        new_spring.resting = new_spring.is_settled();
 
         return new_spring;
     }
@@ -218,98 +408,242 @@ impl SpringVec {
         } else {
             ratio
         };
+        self.response_key = None;
     }
-    
+
     pub fn set_angular(&mut self, angle: f32) {
         self.angular_frequency = if angle < 0.0 {
             0.0
         } else {
             angle
         };
+        self.response_key = None;
     }
 
-    pub fn set_target(&mut self, new_target: Vec2) {
-        //self.position += -new_target;
+    pub fn set_target(&mut self, new_target: T) {
         self.target = new_target;
     }
 
+    //jumps to the target without animating or sending SpringSettled
+    pub fn snap_to_target(&mut self) {
+        self.position = self.target;
+        self.velocity = T::zero();
+        self.resting = true;
+    }
+
     pub fn shove(
         &mut self,
-        goal: Vec2,
+        goal: T,
     ) {
-        self.position += goal;
+        self.position = self.position.add(goal);
     }
 
-    pub fn update(&mut self, delta_time: f32) {
-        if self.angular_frequency < EPSILON {
-            self.pos_coef = 1.0;
-            self.pos_vel_coef = 0.0;
-            self.vel_pos_coef = 0.0;
-            self.vel_coef = 1.0;
+    pub fn is_settled(&self) -> bool {
+        self.position.sub(self.target).length() <= self.settle_distance
+            && self.velocity.length() <= self.settle_speed
+    }
+
+    //settled on the last update and nothing has moved it since
+    pub fn is_resting(&self) -> bool {
+        self.resting && self.is_settled()
+    }
+
+    //returns true on the update the spring comes to rest
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        self.refresh_coefficients(delta_time);
+        self.step()
+    }
+
+    //advances with coefficients computed elsewhere, e.g. once for a whole group of springs
+    pub fn update_with(&mut self, coefs: &SpringCoefficients) -> bool {
+        self.coefs = *coefs;
+        self.coefs_key = None;
+        self.step()
+    }
+
+    fn key(&self) -> SpringKey {
+        SpringKey {
+            angular_frequency: self.angular_frequency,
+            damping_ratio: self.damping_ratio,
+        }
+    }
+
+    //the fields are public, so compare the key as well as relying on the setters
+    fn refresh_coefficients(&mut self, delta_time: f32) {
+        let key = self.key();
+        if self.coefs_key == Some((key, delta_time)) {
             return;
         }
+        if self.response_key != Some(key) {
+            self.response = SpringResponse::new(key);
+            self.response_key = Some(key);
+        }
+        self.coefs = self.response.coefficients(delta_time);
+        self.coefs_key = Some((key, delta_time));
+    }
 
-        if self.damping_ratio > (1.0 + EPSILON) {
-            //over-damped
-            let za = -self.angular_frequency * self.damping_ratio;
-            let zb = self.angular_frequency * f32::sqrt(self.damping_ratio * self.damping_ratio - 1.0);
-            let z1 = za - zb;
-            let z2 = za + zb;
+    fn step(&mut self) -> bool {
+        if self.is_resting() {
+            self.position = self.target;
+            return false;
+        }
+        if self.angular_frequency < EPSILON {
+            return false;
+        }
 
-            let e1 = f32::exp(z1 * delta_time);
-            let e2 = f32::exp(z2 * delta_time);
+        //update spring
+        let old_pos = self.position.sub(self.target);
+        let old_vel = self.velocity;
 
-            let inv_two_zb = 1.0 / (2.0 * zb);
+        self.position = old_pos.scale(self.coefs.pos_coef)
+            .add(old_vel.scale(self.coefs.pos_vel_coef))
+            .add(self.target);
+        self.velocity = old_pos.scale(self.coefs.vel_pos_coef)
+            .add(old_vel.scale(self.coefs.vel_coef));
 
-            let e1_over_twozb = e1 * inv_two_zb;
-            let e2_over_twozb = e2 * inv_two_zb;
+        if !self.is_settled() {
+            self.resting = false;
+            return false;
+        }
 
-            let z1e1_over_twozb = z1 * e1_over_twozb;
-            let z2e2_over_twozb = z2 * e2_over_twozb;
+        //snap so a settled spring sits exactly on its target
+        self.position = self.target;
+        self.velocity = T::zero();
+        let just_settled = !self.resting;
+        self.resting = true;
+        return just_settled;
+    }
+}
 
-            self.pos_coef = e1_over_twozb * z2 - z2e2_over_twozb + e2;
-            self.pos_vel_coef = -e1_over_twozb + e2_over_twozb;
+/*
+    -------------------------------------
+        TRANSFORM SPRINGS
+    -------------------------------------
+*/
 
-            self.vel_pos_coef = (z1e1_over_twozb - z2e2_over_twozb + e2) * z2;
-            self.vel_coef = -z1e1_over_twozb + z2e2_over_twozb;
-        } else if self.damping_ratio < (1.0 - EPSILON) {
-            //under-damped
-            let omega_zeta = self.angular_frequency * self.damping_ratio;
-            let alpha = self.angular_frequency * f32::sqrt(1.0 - (self.damping_ratio * self.damping_ratio));
+//springs that own one field of the entity's Transform
+pub trait TransformSpring: Component {
+    //returns true on the update the spring comes to rest
+    fn update(&mut self, delta_time: f32) -> bool;
+    fn is_resting(&self) -> bool;
+    fn apply(&self, transform: &mut Transform);
+}
 
-            let exp_term = f32::exp(-omega_zeta * delta_time);
-            let cos_term = f32::cos(alpha * delta_time);
-            let sin_term = f32::sin(alpha * delta_time);
+//wraps into -PI..PI
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
 
-            let inv_alpha = 1.0 / alpha;
+//2d rotation around z in radians, always turns the short way round
+#[derive(Component, Reflect, Deref, DerefMut)]
+pub struct AngleSpring(pub Spring<f32>);
 
-            let exp_sin = exp_term * sin_term;
-            let exp_cos = exp_term * cos_term;
-            let expomega_zeta_sin_over_alpha = exp_term * omega_zeta * sin_term * inv_alpha;
+impl AngleSpring {
+    pub fn new(target: f32, angular_freq: f32, damping_ratio: f32) -> Self {
+        let mut spring = Spring::new(target, angular_freq, damping_ratio);
+        spring.snap_to_target();
+        Self(spring)
+    }
 
-            self.pos_coef = exp_cos + expomega_zeta_sin_over_alpha;
-            self.pos_vel_coef = exp_sin * inv_alpha;
+    pub fn set_target(&mut self, new_target: f32) {
+        //keep the position in range so it never winds up, then aim at the closest equivalent angle
+        self.0.position = wrap_angle(self.0.position);
+        self.0.target = self.0.position + wrap_angle(new_target - self.0.position);
+    }
+}
 
-            self.vel_pos_coef = -exp_sin * alpha - omega_zeta * expomega_zeta_sin_over_alpha;
-            self.vel_coef = exp_cos - expomega_zeta_sin_over_alpha;
-        } else {
-            //critically damped
-            let exp_term = f32::exp(-self.angular_frequency * delta_time);
-            let time_exp = delta_time * exp_term;
-            let time_exp_freq = time_exp * self.angular_frequency;
+impl TransformSpring for AngleSpring {
+    fn update(&mut self, delta_time: f32) -> bool {
+        self.0.update(delta_time)
+    }
+
+    fn is_resting(&self) -> bool {
+        self.0.is_resting()
+    }
+
+    fn apply(&self, transform: &mut Transform) {
+        transform.rotation = Quat::from_rotation_z(self.0.position);
+    }
+}
+
+//3d rotation, springs the quaternion components and renormalises
+#[derive(Component, Reflect)]
+pub struct RotationSpring {
+    spring: Spring<Vec4>,
+}
 
-            self.pos_coef = time_exp_freq + exp_term;
-            self.pos_vel_coef = time_exp;
+impl RotationSpring {
+    pub fn new(target: Quat, angular_freq: f32, damping_ratio: f32) -> Self {
+        let mut spring = Spring::new(Vec4::from(target), angular_freq, damping_ratio);
+        spring.snap_to_target();
+        Self { spring: spring }
+    }
+
+    pub fn set_damping(&mut self, ratio: f32) {
+        self.spring.set_damping(ratio);
+    }
+
+    pub fn set_angular(&mut self, angle: f32) {
+        self.spring.set_angular(angle);
+    }
+
+    pub fn set_target(&mut self, new_target: Quat) {
+        self.spring.set_target(Vec4::from(new_target));
+    }
+
+    pub fn target(&self) -> Quat {
+        Quat::from_vec4(self.spring.target).normalize()
+    }
 
-            self.vel_pos_coef = -self.angular_frequency * time_exp_freq;
-            self.vel_coef = -time_exp_freq + exp_term;
+    pub fn rotation(&self) -> Quat {
+        Quat::from_vec4(self.spring.position).normalize()
+    }
+
+    //spins the current rotation by `rotation` and lets the spring bring it back
+    pub fn shove(&mut self, rotation: Quat) {
+        self.spring.position = Vec4::from(rotation * self.rotation());
+    }
+}
+
+impl TransformSpring for RotationSpring {
+    fn update(&mut self, delta_time: f32) -> bool {
+        //q and -q are the same rotation, pick the one on our side to take the shortest path
+        if self.spring.position.dot(self.spring.target) < 0. {
+            self.spring.target = -self.spring.target;
         }
+        self.spring.update(delta_time)
+    }
 
-        //update spring
-        let old_pos = self.position - self.target;
-        let old_vel = self.velocity;
+    fn is_resting(&self) -> bool {
+        self.spring.is_resting()
+    }
 
-        self.position = old_pos * self.pos_coef + old_vel * self.pos_vel_coef + self.target;
-        self.velocity = old_pos * self.vel_pos_coef + old_vel * self.vel_coef;
+    fn apply(&self, transform: &mut Transform) {
+        transform.rotation = self.rotation();
     }
-}
\ No newline at end of file
+}
+
+#[derive(Component, Reflect, Deref, DerefMut)]
+pub struct ScaleSpring(pub Spring<Vec3>);
+
+impl ScaleSpring {
+    pub fn new(target: Vec3, angular_freq: f32, damping_ratio: f32) -> Self {
+        let mut spring = Spring::new(target, angular_freq, damping_ratio);
+        spring.snap_to_target();
+        Self(spring)
+    }
+}
+
+impl TransformSpring for ScaleSpring {
+    fn update(&mut self, delta_time: f32) -> bool {
+        self.0.update(delta_time)
+    }
+
+    fn is_resting(&self) -> bool {
+        self.0.is_resting()
+    }
+
+    fn apply(&self, transform: &mut Transform) {
+        transform.scale = self.0.position;
+    }
+}
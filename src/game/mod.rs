@@ -28,9 +28,9 @@ impl Default for BoidsConfig {
 }
 
 #[derive(Component)]
-struct Boid {
-    velo: Vec2,
-    accel: Vec2
+pub struct Boid {
+    pub velo: Vec2,
+    pub accel: Vec2
 }
 
 pub struct BoidsPlugin;
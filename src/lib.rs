@@ -1,6 +1,8 @@
+pub mod broadphase;
 pub mod core;
 pub mod game;
 pub mod quadtree;
+pub mod xpbd;
 
 use bevy::{
     prelude::*,
@@ -64,8 +66,10 @@ fn setup_camera(mut commands: Commands) {
     let mut trans: Transform = Transform::from_scale(Vec3::new(0.6, 0.6, 1.)); //0.5
     trans.translation = Vec3::new(0.,0.0,999.9);
 
-    let mut proj: OrthographicProjection = OrthographicProjection::default();
-    proj.scale = 1.7;
+    let proj = OrthographicProjection {
+        scale: 1.7,
+        ..default()
+    };
 
     commands.spawn((Camera2dBundle {
             camera: Camera {
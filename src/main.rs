@@ -9,6 +9,7 @@ pub const BACKGROUND_COLOR: Color = Color::hsl(200.,0.9, 0.04);
 //https://github.com/laundmo/bevy_screen_diagnostics
 use bevy_screen_diagnostics::{ScreenDiagnosticsPlugin, ScreenFrameDiagnosticsPlugin};
 use boids::CorePlugin;
+use boids::xpbd::{XpbdPlugin, XpbdSet};
 
 fn main() {
     let mut app = App::new();
@@ -54,12 +55,13 @@ fn main() {
             ScreenFrameDiagnosticsPlugin
     ));
 
-    app.add_plugins(CorePlugin);
+    app.add_plugins((CorePlugin, XpbdPlugin));
 
-    app.add_systems(Startup, boids::quadtree::test_setup);
-    app.add_systems(PreUpdate, (boids::quadtree::draw_quad_rects, boids::quadtree::draw_points));
-    app.add_systems(FixedUpdate, (boids::quadtree::place_point, boids::quadtree::move_points, boids::quadtree::point_collision).chain());
-    app.add_systems(PostUpdate, boids::quadtree::apply_constraint);
+    app.add_systems(Startup, (boids::quadtree::test_setup, boids::xpbd::test_rope, boids::xpbd::test_bodies));
+    app.add_systems(PreUpdate, (boids::broadphase::draw_broadphase, boids::quadtree::draw_points, boids::xpbd::draw_distance_constraints, boids::xpbd::draw_bodies, boids::xpbd::draw_colliders));
+    app.init_resource::<boids::quadtree::PointRequests>();
+    app.add_systems(Update, boids::quadtree::read_point_input);
+    app.add_systems(FixedUpdate, boids::quadtree::place_point.before(XpbdSet::Prepare));
 
     app.run();
 }
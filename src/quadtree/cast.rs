@@ -0,0 +1,239 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::hash::Hash;
+
+use bevy::math::Vec2;
+
+use crate::broadphase::Broadphase;
+use super::{tree::ROOT, QuadTree, Rect};
+
+const EPSILON: f32 = 0.0001;
+
+/*
+    -------------------------------------
+        RAY CASTING
+    -------------------------------------
+*/
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit<T> {
+    pub item: T,
+    pub point: Vec2,
+    pub normal: Vec2,
+    pub distance: f32,
+}
+
+//slab test, returns entry distance and the normal of the face entered through
+//a ray starting inside hits at 0 facing back along the ray
+pub fn ray_aabb(origin: Vec2, dir: Vec2, rect: &Rect, max_dist: f32) -> Option<(f32, Vec2)> {
+    let mut t_min: f32 = 0.;
+    let mut t_max = max_dist;
+    let mut normal = -dir;
+
+    let slabs = [
+        (origin.x, dir.x, rect.left, rect.right, Vec2::X),
+        (origin.y, dir.y, rect.bottom, rect.top, Vec2::Y),
+    ];
+    for (o, d, lo, hi, axis) in slabs {
+        if d.abs() < EPSILON {
+            if o < lo || o > hi {
+                return None;
+            }
+            continue;
+        }
+
+        let inv = 1. / d;
+        let mut t1 = (lo - o) * inv;
+        let mut t2 = (hi - o) * inv;
+        let mut face = -axis;
+        if t1 > t2 {
+            std::mem::swap(&mut t1, &mut t2);
+            face = axis;
+        }
+
+        if t1 > t_min {
+            t_min = t1;
+            normal = face;
+        }
+        t_max = t_max.min(t2);
+        if t_min > t_max {
+            return None;
+        }
+    }
+    return Some((t_min, normal));
+}
+
+pub fn ray_circle(origin: Vec2, dir: Vec2, center: Vec2, radius: f32, max_dist: f32) -> Option<(f32, Vec2)> {
+    let m = origin - center;
+    let b = m.dot(dir);
+    let c = m.length_squared() - radius * radius;
+    //outside and pointing away
+    if c > 0. && b > 0. {
+        return None;
+    }
+
+    let disc = b * b - c;
+    if disc < 0. {
+        return None;
+    }
+
+    let t = -b - disc.sqrt();
+    if t < 0. {
+        return Some((0., -dir));
+    }
+    if t > max_dist {
+        return None;
+    }
+
+    let normal = (origin + dir * t - center) / radius.max(EPSILON);
+    return Some((t, normal));
+}
+
+impl<T: Copy + Eq + Hash> QuadTree<T> {
+    //first element bounds hit along the ray
+    pub fn raycast(&self, origin: Vec2, dir: Vec2, max_dist: f32) -> Option<RayHit<T>> {
+        Broadphase::raycast(self, origin, dir, max_dist)
+    }
+
+    //hit_test does the exact shape test for an element, returning distance and normal, or None to skip it
+    pub fn raycast_with<F>(&self, origin: Vec2, dir: Vec2, max_dist: f32, mut hit_test: F) -> Option<RayHit<T>>
+    where
+        F: FnMut(&T, &Rect) -> Option<(f32, Vec2)>,
+    {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec2::ZERO {
+            return None;
+        }
+
+        let mut best: Option<(T, f32, Vec2)> = None;
+        self.raycast_helper(ROOT, origin, dir, max_dist, &mut hit_test, &mut best);
+
+        return best.map(|(item, distance, normal)| RayHit {
+            item: item,
+            point: origin + dir * distance,
+            normal: normal,
+            distance: distance,
+        });
+    }
+
+    fn raycast_helper<F>(&self, node: usize, origin: Vec2, dir: Vec2, max_dist: f32, hit_test: &mut F, best: &mut Option<(T, f32, Vec2)>)
+    where
+        F: FnMut(&T, &Rect) -> Option<(f32, Vec2)>,
+    {
+        let node = &self.nodes[node];
+        let limit = best.map_or(max_dist, |(_, distance, _)| distance);
+
+        for (item, bounds) in node.elements.iter() {
+            if ray_aabb(origin, dir, bounds, limit).is_none() {
+                continue;
+            }
+            let Some((distance, normal)) = hit_test(item, bounds) else {
+                continue;
+            };
+            if distance <= max_dist && best.is_none_or(|(_, best_distance, _)| distance < best_distance) {
+                *best = Some((*item, distance, normal));
+            }
+        }
+        let Some(children) = node.children() else {
+            return;
+        };
+
+        let limit = best.map_or(max_dist, |(_, distance, _)| distance);
+
+        //visit children in the order the ray enters them, later ones can't beat an earlier hit
+        let mut order: Vec<(f32, usize)> = children
+            .filter_map(|child| ray_aabb(origin, dir, &self.nodes[child].loose_bounds, limit).map(|(t, _)| (t, child)))
+            .collect();
+        order.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (enter, child) in order {
+            if best.is_some_and(|(_, distance, _)| enter > distance) {
+                break;
+            }
+            self.raycast_helper(child, origin, dir, max_dist, hit_test, best);
+        }
+    }
+}
+
+/*
+    -------------------------------------
+        SHAPE CASTING
+    -------------------------------------
+*/
+
+#[derive(Debug, Clone, Copy)]
+pub enum CastShape {
+    Circle(f32),
+    //half extents
+    Aabb(Vec2),
+}
+
+impl CastShape {
+    pub fn bounds(&self, center: Vec2) -> Rect {
+        match self {
+            CastShape::Circle(radius) => Rect::from_center(center, Vec2::splat(*radius)),
+            CastShape::Aabb(half_size) => Rect::from_center(center, *half_size),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeHit<T> {
+    pub item: T,
+    //fraction of the motion travelled before touching, 0 means it already overlaps
+    pub toi: f32,
+    //where the shape's center is at the time of impact
+    pub center: Vec2,
+    //contact point on the surface that was hit
+    pub point: Vec2,
+    //points away from what was hit, towards the moving shape
+    pub normal: Vec2,
+}
+
+//a point against a box with rounded corners, which is what a circle swept past a box looks like
+pub fn ray_rounded_rect(origin: Vec2, dir: Vec2, center: Vec2, half_size: Vec2, radius: f32, max_dist: f32) -> Option<(f32, Vec2)> {
+    let outer = Rect::from_center(center, half_size + Vec2::splat(radius));
+    let (t, normal) = ray_aabb(origin, dir, &outer, max_dist)?;
+
+    //entering through a corner square, only the quarter circle in it is solid
+    let local = origin + dir * t - center;
+    if radius > 0. && local.x.abs() > half_size.x && local.y.abs() > half_size.y {
+        let corner = center + Vec2::new(half_size.x.copysign(local.x), half_size.y.copysign(local.y));
+        return ray_circle(origin, dir, corner, radius, max_dist);
+    }
+    return Some((t, normal));
+}
+
+//sweeps shape against an element's bounds as if they were a box
+pub fn sweep_aabb(shape: &CastShape, origin: Vec2, dir: Vec2, bounds: &Rect, max_dist: f32) -> Option<(f32, Vec2)> {
+    match shape {
+        CastShape::Circle(radius) => ray_rounded_rect(origin, dir, bounds.center(), bounds.half_size, *radius, max_dist),
+        CastShape::Aabb(half_size) => {
+            let expanded = Rect::from_center(bounds.center(), bounds.half_size + *half_size);
+            ray_aabb(origin, dir, &expanded, max_dist)
+        }
+    }
+}
+
+//sweeps shape against a circle
+pub fn sweep_circle(shape: &CastShape, origin: Vec2, dir: Vec2, center: Vec2, radius: f32, max_dist: f32) -> Option<(f32, Vec2)> {
+    match shape {
+        CastShape::Circle(shape_radius) => ray_circle(origin, dir, center, radius + shape_radius, max_dist),
+        CastShape::Aabb(half_size) => ray_rounded_rect(origin, dir, center, *half_size, radius, max_dist),
+    }
+}
+
+impl<T: Copy + Eq + Hash> QuadTree<T> {
+    //earliest element bounds the shape touches while moving from origin by motion
+    pub fn shape_cast(&self, shape: &CastShape, origin: Vec2, motion: Vec2) -> Option<ShapeHit<T>> {
+        Broadphase::shape_cast(self, shape, origin, motion)
+    }
+
+    //hit_test does the exact sweep against an element, returning distance along the motion and normal, or None to skip it
+    pub fn shape_cast_with<F>(&self, shape: &CastShape, origin: Vec2, motion: Vec2, mut hit_test: F) -> Option<ShapeHit<T>>
+    where
+        F: FnMut(&T, &Rect) -> Option<(f32, Vec2)>,
+    {
+        Broadphase::shape_cast_with(self, shape, origin, motion, &mut hit_test)
+    }
+}
@@ -1,21 +1,63 @@
-use std::thread;
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
 
 use bevy::{prelude::*, window::PrimaryWindow};
 use rand::Rng;
 
-use super::QuadTree;
+use crate::broadphase::EntityBroadphase;
+use super::Rect;
+
+//a default point, bigger and smaller ones keep the same density
+pub const POINT_RADIUS: f32 = 10.;
+pub const POINT_MASS: f32 = 1.;
 
 #[derive(Component)]
 pub struct Point {
-    accel: Vec2,
-    velo: Vec2,
-    last_pos: Vec2,
-    radius: f32
+    //external acceleration, applied for one solver step and then cleared
+    pub accel: Vec2,
+    pub velo: Vec2,
+    pub last_pos: Vec2,
+    pub radius: f32,
+    //0 pins the point in place
+    pub inv_mass: f32
 }
 
 impl Point {
-    fn new(accel: Vec2) -> Self {
-        Self { accel: accel, velo: Vec2::ZERO, radius: 10., last_pos: Vec2::default() }
+    pub fn new(accel: Vec2) -> Self {
+        Self::with_radius(accel, POINT_RADIUS)
+    }
+
+    //mass grows with area, so a point twice as wide weighs four times as much
+    pub fn with_radius(accel: Vec2, radius: f32) -> Self {
+        let scale = radius / POINT_RADIUS;
+        let mut point = Self { accel: accel, velo: Vec2::ZERO, radius: radius, last_pos: Vec2::default(), inv_mass: 0. };
+        point.set_mass(POINT_MASS * scale * scale);
+        return point;
+    }
+
+    //infinite mass, contacts and constraints push everything else out of it
+    pub fn new_static(radius: f32) -> Self {
+        Self { accel: Vec2::ZERO, velo: Vec2::ZERO, radius: radius, last_pos: Vec2::default(), inv_mass: 0. }
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.inv_mass == 0.
+    }
+
+    pub fn mass(&self) -> f32 {
+        if self.is_static() {
+            return f32::INFINITY;
+        }
+        return 1. / self.inv_mass;
+    }
+
+    //zero, negative or infinite mass makes the point static
+    pub fn set_mass(&mut self, mass: f32) {
+        self.inv_mass = if mass > 0. && mass.is_finite() { 1. / mass } else { 0. };
+    }
+
+    //what the quad tree stores for a point at pos
+    pub fn bounds(&self, pos: Vec2) -> Rect {
+        Rect::from_center(pos, Vec2::splat(self.radius))
     }
 }
 
@@ -42,191 +84,99 @@ fn gen_dir() -> f32 {
     return num;
 }
 
+//a point the cursor asked for, spawned on the next physics tick
+pub struct PointRequest {
+    pub pos: Vec2,
+    pub pinned: bool,
+}
+
+//what the cursor wants placed, filled every frame and emptied every physics tick
+#[derive(Resource, Default)]
+pub struct PointRequests {
+    //clicks, a queue rather than an event since fixed update can skip frames and events only live for two
+    pub clicks: Vec<PointRequest>,
+    //where the left button is held, streams one point per physics tick rather than one per frame
+    pub held: Option<Vec2>,
+}
+
 //SOURCES
 // https://bevy-cheatbook.github.io/cookbook/cursor2world.html
 // https://bevy-cheatbook.github.io/input/mouse.html
-pub fn place_point(
+//reads the mouse every frame, so clicks between physics ticks aren't missed
+pub fn read_point_input(
     // query to get the window (so we can read the current cursor position)
     q_window: Query<&Window, With<PrimaryWindow>>,
     // query to get camera transform
-    q_camera: Query<(&Camera, &GlobalTransform), With<crate::MainCamera>>,   
-    buttons: Res<Input<MouseButton>>,   
-    mut commands: Commands,
-    parent_point: Query<Entity, With<PointParent>>,
-    mut quad_tree: ResMut<QuadTree>,
-    //mut gizmos: Gizmos
+    q_camera: Query<(&Camera, &GlobalTransform), With<crate::MainCamera>>,
+    buttons: Res<Input<MouseButton>>,
+    mut requests: ResMut<PointRequests>,
 ) {
-    if parent_point.is_empty() {
-        return;
-    }
-
+    requests.held = None;
     let (camera, camera_transform) = q_camera.single();
     let window = q_window.single();
 
-    if let Some(world_pos) = window.cursor_position()
+    let Some(world_pos) = window.cursor_position()
         .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
-        .map(|ray| ray.origin.truncate()) {
-            if world_pos.x < 0. || world_pos.x > 800. || world_pos.y > 0. || world_pos.y < -700. {
-                return;
-            }
-            if !buttons.pressed(MouseButton::Left) {
-                return;
-            }
-            let parent = parent_point.single();
-            
-            for i in 0..1 {
-                let mut rng = rand::thread_rng();
-                let x = gen_dir();
-                let y = gen_dir();//rng.gen_range(0.5..1.);
-                let speed = rng.gen_range(1.0..25.);
-                let offset = i as f32 * 3.;
-
-                let pos = Vec3::new(world_pos.x + offset, world_pos.y,1.);
-                let transform = Transform::from_translation(pos);
-                let mut point = Point::new(Vec2::new(x as f32, y as f32).normalize() * speed);
-                point.last_pos = pos.truncate();
-
-                let ent = commands.spawn((
-                    point,
-                    transform,
-                    //Name::new(format!("point"))
-                )).id();
-                commands.entity(parent).add_child(ent);
-                
-                quad_tree.insert_point(&ent, Vec2::new(world_pos.x, world_pos.y));
-            }
-            /* 
-            let rect = crate::quadtree::Rect::new(Vec2::new(world_pos.x, world_pos.y), Vec2::new(30.,30.));
-            let overlaps = rect.overlaps(&quad_tree.bounds);
-            if overlaps {
-                let pos = rect.pos + Vec2::new(rect.size.x/2., -rect.size.y/2.);
-                gizmos.rect_2d(pos, 0., rect.size, Color::RED);
-            } else {
-                let pos = rect.pos + Vec2::new(rect.size.x/2., -rect.size.y/2.);
-                gizmos.rect_2d(pos, 0., rect.size, Color::WHITE);
-            }
-            */
-            //println!("World coords: {}/{}", world_position.x, world_position.y);
+        .map(|ray| ray.origin.truncate()) else {
+        return;
+    };
+    if world_pos.x < 0. || world_pos.x > 800. || world_pos.y > 0. || world_pos.y < -700. {
+        return;
     }
-}
-
-pub fn move_points(
-    mut q_point: Query<(Entity, &mut Point, &mut Transform)>,
-    mut quad_tree: ResMut<QuadTree>,
-    time: Res<Time>
-) {
-    quad_tree.clear();
-    for (ent, mut point, mut transform) in q_point.iter_mut() {
-        let position = transform.translation.truncate();
-        let accel = point.accel + Vec2::new(0.,-370.);
-        let displacement = position - point.last_pos;
-        let delta = time.delta_seconds();
-
-        point.last_pos = position;
 
-        //50 is damping
-        let new_pos = position + displacement + (accel - displacement * 40.) * (delta * delta);
-        transform.translation = new_pos.extend(1.);
-
-        point.accel *= 0.;
-
-        quad_tree.insert_point(&ent, transform.translation.truncate());
+    //right click pins a static point in place
+    if buttons.just_pressed(MouseButton::Right) {
+        requests.clicks.push(PointRequest { pos: world_pos, pinned: true });
     }
-}
-
-pub fn apply_constraint(
-    mut q_point: Query<(Entity, &mut Point, &mut Transform)>,
-    quad_tree: ResMut<QuadTree>,
-) {
-    for (_ent, mut point, mut transform) in q_point.iter_mut() {
-        let quad_pos = quad_tree.bounds.pos;
-        let quad_size = quad_tree.bounds.size;
-        let p_pos = transform.translation.truncate();
-        let padding:f32 = 1.0;
-        let rad: f32 = 10.+padding;
-
-        //left check
-        if p_pos.x-rad <= quad_pos.x {
-            let dist = quad_pos.x + rad;
-            transform.translation.x = dist;
-            point.last_pos.x = dist
-            //point.velo.x *= -1.;
-        }
-    
-        //right check
-        if p_pos.x+rad >= quad_pos.x + quad_size.x {
-            let dist = quad_pos.x + quad_size.x - rad;
-            transform.translation.x = dist;
-            point.last_pos.x = dist
-            //point.velo.x *= -1.;
-        }
-
-        //top check
-        if p_pos.y+rad >= quad_pos.y {
-            let dist = quad_pos.y - rad;
-            transform.translation.y = dist;
-            point.last_pos.y = dist
-            //point.velo.y *= -1.;
-        }
-    
-        //bottom check
-        if p_pos.y-rad <= quad_pos.y - quad_size.y {
-            let dist = quad_pos.y - quad_size.y + rad;
-            transform.translation.y = dist;
-            point.last_pos.y = dist
-            //point.velo.y *= 0.;
-            //point.accel.y += 10.;
-        }
+    //the click itself, so a tap between two ticks still places a point
+    if buttons.just_pressed(MouseButton::Left) {
+        requests.clicks.push(PointRequest { pos: world_pos, pinned: false });
+    }
+    if buttons.pressed(MouseButton::Left) {
+        requests.held = Some(world_pos);
     }
 }
 
-pub fn point_collision(
-    mut q_point: Query<(Entity, &mut Point, &mut Transform)>,
-    quad_tree: Res<QuadTree>,
+pub fn place_point(
+    mut requests: ResMut<PointRequests>,
+    mut commands: Commands,
+    parent_point: Query<Entity, With<PointParent>>,
+    mut broadphase: ResMut<EntityBroadphase>,
 ) {
-    for _ in 0..2 {
-    let mut results: Vec<(Entity, Entity)> = Vec::new();
-    let eps = 0.0001;
-    let response_coef = 0.75;
-
-    for (ent, mut _point, transform) in q_point.iter_mut() {
-        let a = transform.translation.truncate();
-        let rect = crate::quadtree::Rect::new(Vec2::new(a.x-10., a.y+10.),Vec2::new(20.,20.));
-        let query = quad_tree.query_area(&rect);
-
-        //println!("query={:?}", query);
-        'inner: for (o_ent, b) in query.iter() {
-            let b_ent = o_ent.unwrap();
-            if ent == b_ent {
-                continue 'inner
-            }
-                
-            let o2_o1 = a - *b;
-            let d2 = o2_o1.x * o2_o1.x + o2_o1.y * o2_o1.y;
-            let rad = 20.0*20.0;
-            if d2 <= rad+21. && d2 > eps {
-                results.push((ent, b_ent));
-            }
-        }
-        //println!("d={}",d);
-    }
-
-    for (ent_a, ent_b) in results.iter() {
-        let mut q = q_point.get_many_mut([*ent_a, *ent_b]).unwrap();
-
-        let o2_o1 = q[0].2.translation.truncate() - q[1].2.translation.truncate();
-        let d2 = o2_o1.x * o2_o1.x + o2_o1.y * o2_o1.y;
+    let Ok(parent) = parent_point.get_single() else {
+        requests.clicks.clear();
+        return;
+    };
 
-        //println!("d2={}", d2);
-        if d2 <= 21.0*21.0 && d2 > eps {
-            let dist = d2.sqrt();
-            let delta = 0.5 * response_coef * (21.0 - dist);
-            let col_vec = (o2_o1 / dist) * delta;
-            //println!("col_vec={}",col_vec);
-            q[0].2.translation += col_vec.extend(0.);
-            q[1].2.translation -= col_vec.extend(0.);
+    //a held button adds one point a tick, unless a click already placed this tick's
+    let mut placing: Vec<PointRequest> = requests.clicks.drain(..).collect();
+    if let Some(pos) = requests.held {
+        if placing.iter().all(|request| request.pinned) {
+            placing.push(PointRequest { pos: pos, pinned: false });
         }
     }
-    }
 
-}
\ No newline at end of file
+    for request in placing {
+        let mut point = if request.pinned {
+            Point::new_static(POINT_RADIUS * 2.)
+        } else {
+            let mut rng = rand::thread_rng();
+            let x = gen_dir();
+            let y = gen_dir();
+            let speed = rng.gen_range(1.0..25.);
+            //a mix of pebbles and boulders
+            let radius = rng.gen_range(0.5..2.) * POINT_RADIUS;
+            Point::with_radius(Vec2::new(x, y).normalize() * speed, radius)
+        };
+        point.last_pos = request.pos;
+
+        let bounds = point.bounds(request.pos);
+        let ent = commands.spawn((
+            point,
+            Transform::from_translation(request.pos.extend(1.)),
+        )).id();
+        commands.entity(parent).add_child(ent);
+
+        broadphase.insert(&ent, &bounds);
+    }
+}
@@ -0,0 +1,138 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::{cmp::Ordering, collections::BinaryHeap, hash::Hash};
+
+use bevy::{math::Vec2, utils::HashSet};
+
+use super::{tree::ROOT, Element, QuadTree, Rect};
+
+/*
+    -------------------------------------
+        DISTANCE QUERIES
+    -------------------------------------
+*/
+
+//nodes and elements waiting in the best-first queue, closest first
+enum Candidate<T> {
+    Node(usize),
+    Element(T),
+}
+
+struct Queued<T> {
+    dist_sq: f32,
+    candidate: Candidate<T>,
+}
+
+impl<T> PartialEq for Queued<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.dist_sq == other.dist_sq
+    }
+}
+
+impl<T> Eq for Queued<T> {}
+
+impl<T> PartialOrd for Queued<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Queued<T> {
+    //reversed so the BinaryHeap pops the smallest distance
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.dist_sq.total_cmp(&self.dist_sq)
+    }
+}
+
+impl<T: Copy + Eq + Hash> QuadTree<T> {
+    //every element whose circle comes within radius of center, each reported once
+    //elements are taken as the circle inscribed in their bounds, so a bounds corner near center doesn't count
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<Element<T>> {
+        let area = Rect::from_center(center, Vec2::splat(radius));
+
+        let mut elements = self.query_area(&area);
+        elements.retain(|(_, bounds)| bounds.circle_distance_to(center) <= radius);
+        return elements;
+    }
+
+    //the k elements closest to point with their distance, nearest first
+    pub fn k_nearest(&self, point: Vec2, k: usize) -> Vec<(T, f32)> {
+        let mut found: Vec<(T, f32)> = Vec::with_capacity(k);
+        if k == 0 {
+            return found;
+        }
+
+        let mut seen: HashSet<T> = HashSet::new();
+        let mut queue: BinaryHeap<Queued<T>> = BinaryHeap::new();
+        queue.push(Queued { dist_sq: self.root().loose_bounds.distance_squared_to(point), candidate: Candidate::Node(ROOT) });
+
+        //an element only comes off the queue once nothing left can be closer
+        while let Some(Queued { dist_sq, candidate }) = queue.pop() {
+            match candidate {
+                Candidate::Element(item) => {
+                    found.push((item, dist_sq.sqrt()));
+                    if found.len() == k {
+                        break;
+                    }
+                }
+                Candidate::Node(node) => {
+                    let node = &self.nodes[node];
+                    //loose nodes keep elements of their own even when divided
+                    for (item, bounds) in node.elements.iter() {
+                        if seen.insert(*item) {
+                            queue.push(Queued { dist_sq: bounds.distance_squared_to(point), candidate: Candidate::Element(*item) });
+                        }
+                    }
+                    for child in node.children().into_iter().flatten() {
+                        let dist_sq = self.nodes[child].loose_bounds.distance_squared_to(point);
+                        queue.push(Queued { dist_sq: dist_sq, candidate: Candidate::Node(child) });
+                    }
+                }
+            }
+        }
+        return found;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn query_radius_skips_bounds_corners() {
+        let mut tree: QuadTree<u32> = QuadTree::new(Vec2::new(0., 0.), Vec2::new(100., 100.), 4);
+        tree.insert(&0, &Rect::from_center(Vec2::new(50., -50.), Vec2::splat(10.)));
+
+        //14 from the center diagonally is inside the bounds' corner but 4 away from the circle
+        let near_corner = Vec2::new(50., -50.) + Vec2::splat(14. / 2f32.sqrt());
+        assert!(tree.query_radius(near_corner, 3.).is_empty());
+        assert_eq!(tree.query_radius(near_corner, 4.5).len(), 1);
+    }
+
+    #[test]
+    fn query_radius_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut tree: QuadTree<u32> = QuadTree::new(Vec2::new(0., 0.), Vec2::new(1000., 800.), 4);
+        let mut circles = Vec::new();
+        for i in 0..400 {
+            let (center, radius) = (Vec2::new(rng.gen_range(0.0..1000.), rng.gen_range(-800.0..0.)), rng.gen_range(2.0..20.));
+            tree.insert(&i, &Rect::from_center(center, Vec2::splat(radius)));
+            circles.push((center, radius));
+        }
+
+        for _ in 0..100 {
+            let (center, radius) = (Vec2::new(rng.gen_range(0.0..1000.), rng.gen_range(-800.0..0.)), rng.gen_range(0.0..100.));
+            let mut found: Vec<u32> = tree.query_radius(center, radius).iter().map(|(item, _)| *item).collect();
+            found.sort_unstable();
+            let expected: Vec<u32> = (0..400)
+                .filter(|i| {
+                    let (c, r) = circles[*i as usize];
+                    c.distance(center) - r <= radius
+                })
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
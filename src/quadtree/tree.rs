@@ -0,0 +1,805 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::{hash::Hash, ops::Range};
+
+use bevy::{math::Vec2, utils::{HashMap, HashSet}};
+
+/*
+    plain data structures, nothing in here touches the ECS
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub half_size: Vec2,
+    pub right: f32,
+    pub left: f32,
+    pub top: f32,
+    pub bottom: f32
+}
+
+impl Default for Rect {
+    fn default() -> Self {
+        Rect {
+            pos: Vec2 { x: 0.0, y: 0.0 },
+            size: Vec2 { x: 1.0, y: 1.0 },
+            half_size: Vec2 { x: 1.0, y: 1.0 } / 2.,
+            right: 0.0 + 1.,
+            left: 0.0,
+            top: 0.0,
+            bottom: 0.0 - 1.,
+        }
+    }
+}
+
+impl Rect {
+    pub fn new(pos: Vec2, size: Vec2) -> Self {
+        Rect { 
+            pos: pos, 
+            size: size,
+            half_size: size / 2.,
+            right: pos.x + size.x,
+            left: pos.x,
+            top: pos.y,
+            bottom: pos.y - size.y, 
+        }
+    }
+
+    //zero sized rect, how points are stored in the tree
+    pub fn from_point(p: Vec2) -> Self {
+        Rect::new(p, Vec2::ZERO)
+    }
+
+    pub fn from_center(center: Vec2, half_size: Vec2) -> Self {
+        Rect::new(center + Vec2::new(-half_size.x, half_size.y), half_size * 2.)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.pos + Vec2::new(self.half_size.x, -self.half_size.y)
+    }
+
+    //0 when p is inside
+    pub fn distance_squared_to(&self, p: Vec2) -> f32 {
+        let closest = Vec2::new(p.x.clamp(self.left, self.right), p.y.clamp(self.bottom, self.top));
+        closest.distance_squared(p)
+    }
+
+    //distance from p to the circle inscribed in the rect, 0 inside it, points are stored as that circle's bounds
+    pub fn circle_distance_to(&self, p: Vec2) -> f32 {
+        (self.center().distance(p) - self.half_size.min_element()).max(0.)
+    }
+
+    pub fn contains(&self, p: Vec2) -> bool {
+        (self.left <= p.x && p.x < self.right) && (self.top >= p.y && p.y > self.bottom)
+    }
+
+    //r lies strictly inside, so it can't touch a neighbouring node
+    pub fn contains_rect(&self, r: &Rect) -> bool {
+        self.left < r.left &&
+        r.right < self.right &&
+        r.top < self.top &&
+        self.bottom < r.bottom
+    }
+
+    //r lies inside or on the edge
+    pub fn encloses(&self, r: &Rect) -> bool {
+        self.left <= r.left &&
+        r.right <= self.right &&
+        r.top <= self.top &&
+        self.bottom <= r.bottom
+    }
+
+    //smallest rect holding both, the edges are copied so it encloses them exactly
+    pub fn union(&self, r: &Rect) -> Rect {
+        let mut union = Rect::new(Vec2::new(self.left.min(r.left), self.top.max(r.top)), Vec2::ZERO);
+        union.right = self.right.max(r.right);
+        union.bottom = self.bottom.min(r.bottom);
+        union.size = Vec2::new(union.right - union.left, union.top - union.bottom);
+        union.half_size = union.size / 2.;
+        return union;
+    }
+
+    //grown by amount on every side
+    pub fn grow(&self, amount: f32) -> Rect {
+        Rect::from_center(self.center(), self.half_size + Vec2::splat(amount))
+    }
+
+    pub fn perimeter(&self) -> f32 {
+        2. * (self.size.x + self.size.y)
+    }
+
+    //like overlaps but touching edges count, so zero sized rects still hit
+    pub fn intersects(&self, r: &Rect) -> bool {
+        r.left <= self.right &&
+        self.left <= r.right &&
+        r.top >= self.bottom &&
+        self.top >= r.bottom
+    }
+
+    
+    pub fn overlaps(&self, r: &Rect) -> bool {
+        r.left < self.right && 
+        self.left < r.right &&
+        r.top > self.bottom && 
+        self.top > r.bottom
+    }
+
+}
+
+//one indexed payload and its bounds
+pub type Element<T> = (T, Rect);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeMode {
+    //an element is stored in every leaf its bounds touch
+    Strict,
+    //every node's bounds are grown by this factor around its center,
+    //an element is stored once, in the deepest node that fully holds it
+    Loose(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeSettings {
+    pub mode: TreeMode,
+    //elements a leaf takes before it splits
+    pub capacity: usize,
+    //deepest height a node can be created at, the root is 0
+    pub max_depth: usize,
+    //a node whose children would be smaller than this on either axis stays a leaf
+    pub min_node_size: f32,
+}
+
+impl Default for TreeSettings {
+    fn default() -> Self {
+        Self {
+            mode: TreeMode::Strict,
+            capacity: 10,
+            max_depth: 5,
+            min_node_size: 0.,
+        }
+    }
+}
+
+impl TreeSettings {
+    //loose tree whose smallest nodes fit a couple of objects of object_radius, deep enough to reach them
+    pub fn auto(world_size: Vec2, object_radius: f32) -> Self {
+        let min_node_size = (object_radius * 4.).max(1.);
+        let levels = (world_size.max_element() / min_node_size).max(1.).log2().ceil();
+        Self {
+            mode: TreeMode::Loose(2.),
+            capacity: 8,
+            max_depth: levels as usize,
+            min_node_size: min_node_size,
+        }
+    }
+}
+
+//how full the leaves are, to check the settings suit a scene
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub empty_leaves: usize,
+    //leaves over capacity that weren't allowed to split
+    pub overfull_leaves: usize,
+    pub deepest: usize,
+    //copies held by the nodes, more than tracked when strict elements straddle leaves
+    pub stored: usize,
+    pub tracked: usize,
+    pub max_leaf_elements: usize,
+    pub mean_leaf_elements: f32,
+}
+
+//the root always sits first in the arena
+pub const ROOT: usize = 0;
+
+#[derive(Debug)]
+pub struct Node<T> {
+    pub parent: Option<usize>,
+    //the four children sit next to each other in the arena, starting here
+    pub first_child: Option<usize>,
+    pub height: usize,
+    pub bounds: Rect,
+    //what queries test against, the same as bounds unless the tree is loose
+    pub loose_bounds: Rect,
+    //strict trees only keep elements in leaves, loose ones in any node
+    pub elements: Vec<Element<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(parent: Option<usize>, bounds: Rect, settings: &TreeSettings, height: usize) -> Self {
+        let mut node = Self {
+            parent: None,
+            first_child: None,
+            height: 0,
+            bounds: bounds,
+            loose_bounds: bounds,
+            elements: Vec::new(),
+        };
+        node.reset(parent, bounds, settings, height);
+        node
+    }
+
+    //reuses a freed node, its elements are already empty and keep their allocation
+    fn reset(&mut self, parent: Option<usize>, bounds: Rect, settings: &TreeSettings, height: usize) {
+        self.parent = parent;
+        self.first_child = None;
+        self.height = height;
+        self.bounds = bounds;
+        self.loose_bounds = match settings.mode {
+            TreeMode::Strict => bounds,
+            TreeMode::Loose(looseness) => Rect::from_center(bounds.center(), bounds.half_size * looseness),
+        };
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.first_child.is_none()
+    }
+
+    pub fn children(&self) -> Option<Range<usize>> {
+        self.first_child.map(|first| first..first + 4)
+    }
+}
+
+//T is whatever identifies an object to the caller, an Entity, an index, an id...
+#[derive(Debug)]
+pub struct QuadTree<T> {
+    //every node ever created, the root first and children in blocks of four
+    pub nodes: Vec<Node<T>>,
+    //blocks of children given back by collapse or clear, used before the arena grows
+    free_blocks: Vec<usize>,
+    pub settings: TreeSettings,
+    //the bounds every tracked payload was inserted with
+    pub tracked: HashMap<T, Rect>,
+}
+
+impl<T: Copy + Eq + Hash> QuadTree<T> {
+    pub fn new(pos: Vec2, size: Vec2, capacity: usize) -> Self {
+        Self::with_settings(pos, size, TreeSettings { capacity: capacity, ..Default::default() })
+    }
+
+    //a looseness of 2 lets any element up to the size of a node settle in it
+    pub fn new_loose(pos: Vec2, size: Vec2, capacity: usize, looseness: f32) -> Self {
+        Self::with_settings(pos, size, TreeSettings {
+            mode: TreeMode::Loose(looseness),
+            capacity: capacity,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_settings(pos: Vec2, size: Vec2, mut settings: TreeSettings) -> Self {
+        //below 1 a node would be smaller than its cell, and elements that fit the cell wouldn't fit the node
+        if let TreeMode::Loose(looseness) = settings.mode {
+            settings.mode = TreeMode::Loose(looseness.max(1.));
+        }
+        Self {
+            nodes: vec![Node::new(None, Rect::new(pos, size), &settings, 0)],
+            free_blocks: Vec::new(),
+            settings: settings,
+            tracked: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Node<T> {
+        &self.nodes[ROOT]
+    }
+
+    pub fn bounds(&self) -> &Rect {
+        &self.nodes[ROOT].bounds
+    }
+
+    pub fn insert_point(&mut self, item: &T, point: Vec2) -> bool {
+        self.insert(item, &Rect::from_point(point))
+    }
+
+    pub fn insert(&mut self, item: &T, rect: &Rect) -> bool {
+        if self.tracked.contains_key(item) {
+            return self.update(item, rect);
+        }
+
+        if !self.insert_from(ROOT, item, rect) {
+            return false;
+        }
+        self.tracked.insert(*item, *rect);
+        return true;
+    }
+
+    pub fn update_point(&mut self, item: &T, new_point: Vec2) -> bool {
+        self.update(item, &Rect::from_point(new_point))
+    }
+
+    //moves a tracked entity, the element is only relocated when it stops fitting inside its current leaf
+    pub fn update(&mut self, item: &T, new_rect: &Rect) -> bool {
+        let Some(old_rect) = self.tracked.get(item).copied() else {
+            return self.insert(item, new_rect);
+        };
+
+        if old_rect == *new_rect {
+            return true;
+        }
+
+        //a loose element can stay put as long as inserting it again would land in the same node
+        if let TreeMode::Loose(_) = self.settings.mode {
+            if !self.nodes[ROOT].loose_bounds.encloses(new_rect) {
+                self.remove(item);
+                return false;
+            }
+            let node = self.find_loose_node(ROOT, new_rect);
+            if let Some(element) = self.nodes[node].elements.iter_mut().find(|(e, _)| *e == *item) {
+                element.1 = *new_rect;
+                self.tracked.insert(*item, *new_rect);
+                return true;
+            }
+        }
+        //strictly inside one leaf means it is stored nowhere else
+        else if let Some(leaf) = self.find_leaf(&old_rect) {
+            let leaf = &mut self.nodes[leaf];
+            if leaf.bounds.contains_rect(new_rect) {
+                if let Some(element) = leaf.elements.iter_mut().find(|(e, _)| *e == *item) {
+                    element.1 = *new_rect;
+                    self.tracked.insert(*item, *new_rect);
+                    return true;
+                }
+            }
+        }
+
+        self.remove(item);
+        return self.insert(item, new_rect);
+    }
+
+    pub fn remove(&mut self, item: &T) -> bool {
+        let Some(rect) = self.tracked.remove(item) else {
+            return false;
+        };
+        return match self.settings.mode {
+            TreeMode::Strict => self.remove_element(ROOT, item, &rect),
+            TreeMode::Loose(_) => self.remove_loose(item, rect.center()),
+        };
+    }
+
+    //the child of node whose quarter holds p
+    fn child_of(&self, node: usize, p: Vec2) -> Option<usize> {
+        let node = &self.nodes[node];
+        let first = node.first_child?;
+        let center = node.bounds.center();
+        Some(first + (p.x >= center.x) as usize + 2 * (p.y < center.y) as usize)
+    }
+
+    //the node a loose insert of rect ends up in, walking down from start by its center
+    fn find_loose_node(&self, start: usize, rect: &Rect) -> usize {
+        let mut node = start;
+        while let Some(child) = self.child_of(node, rect.center()) {
+            if !self.nodes[child].loose_bounds.encloses(rect) {
+                break;
+            }
+            node = child;
+        }
+        return node;
+    }
+
+    //the leaf strictly containing rect, if it sits inside a single one
+    fn find_leaf(&self, rect: &Rect) -> Option<usize> {
+        if !self.nodes[ROOT].bounds.contains_rect(rect) {
+            return None;
+        }
+
+        let mut node = ROOT;
+        while let Some(children) = self.nodes[node].children() {
+            node = children.into_iter().find(|child| self.nodes[*child].bounds.contains_rect(rect))?;
+        }
+        return Some(node);
+    }
+
+    fn remove_element(&mut self, node: usize, item: &T, rect: &Rect) -> bool {
+        if !self.nodes[node].bounds.intersects(rect) {
+            return false;
+        }
+
+        let Some(children) = self.nodes[node].children() else {
+            let elements = &mut self.nodes[node].elements;
+            let Some(index) = elements.iter().position(|(e, _)| *e == *item) else {
+                return false;
+            };
+            elements.swap_remove(index);
+            return true;
+        };
+
+        let mut removed = false;
+        for child in children {
+            removed |= self.remove_element(child, item, rect);
+        }
+
+        if removed {
+            self.collapse(node);
+        }
+        return removed;
+    }
+
+    //a loose element sits somewhere on the path down to its center, so only that path is searched
+    fn remove_loose(&mut self, item: &T, center: Vec2) -> bool {
+        let mut node = ROOT;
+        loop {
+            let elements = &mut self.nodes[node].elements;
+            if let Some(index) = elements.iter().position(|(e, _)| *e == *item) {
+                elements.swap_remove(index);
+                break;
+            }
+            let Some(child) = self.child_of(node, center) else {
+                return false;
+            };
+            node = child;
+        }
+
+        //walk back up, each ancestor can only fold once the one below it has
+        let mut current = if self.nodes[node].is_leaf() { self.nodes[node].parent } else { Some(node) };
+        while let Some(index) = current {
+            if !self.collapse(index) {
+                break;
+            }
+            current = self.nodes[index].parent;
+        }
+        return true;
+    }
+
+    //folds the children back into node once they are all leaves holding less than capacity
+    fn collapse(&mut self, node: usize) -> bool {
+        let Some(children) = self.nodes[node].children() else {
+            return false;
+        };
+
+        //loose nodes can hold elements of their own on top of the children's
+        let mut merged: Vec<Element<T>> = self.nodes[node].elements.clone();
+        for child in children.clone() {
+            let child = &self.nodes[child];
+            if !child.is_leaf() {
+                return false;
+            }
+            for element in child.elements.iter() {
+                //straddling elements live in several children but only need one copy here
+                if !merged.iter().any(|(e, _)| *e == element.0) {
+                    merged.push(*element);
+                }
+            }
+        }
+
+        if merged.len() >= self.settings.capacity {
+            return false;
+        }
+
+        for child in children.clone() {
+            self.nodes[child].elements.clear();
+        }
+        self.nodes[node].elements = merged;
+        self.nodes[node].first_child = None;
+        self.free_blocks.push(children.start);
+        return true;
+    }
+
+    fn insert_from(&mut self, node: usize, item: &T, rect: &Rect) -> bool {
+        match self.settings.mode {
+            TreeMode::Strict => self.insert_element(node, item, rect),
+            TreeMode::Loose(_) => self.insert_loose(node, item, rect),
+        }
+    }
+
+    fn insert_element(&mut self, node: usize, item: &T, rect: &Rect) -> bool {
+        if !self.nodes[node].bounds.intersects(rect) {
+            //println!("does not contain");
+            return false;
+        }
+
+        if let Some(children) = self.nodes[node].children() {
+            //println!("push element to every branch it touches");
+            let mut inserted = false;
+            for child in children {
+                inserted |= self.insert_element(child, item, rect);
+            }
+            return inserted;
+        }
+
+        //println!("push element");
+        self.nodes[node].elements.push((*item, *rect));
+        if self.nodes[node].elements.len() >= self.settings.capacity && self.can_subdivide(node) {
+            self.subdivide(node);
+        }
+
+        return true;
+    }
+
+    fn insert_loose(&mut self, start: usize, item: &T, rect: &Rect) -> bool {
+        if start == ROOT && !self.nodes[ROOT].loose_bounds.encloses(rect) {
+            return false;
+        }
+
+        //too big for the child its center falls in means it belongs to the parent
+        let node = self.find_loose_node(start, rect);
+        self.nodes[node].elements.push((*item, *rect));
+
+        if self.nodes[node].is_leaf() && self.nodes[node].elements.len() >= self.settings.capacity && self.can_subdivide(node) {
+            self.subdivide(node);
+        }
+        return true;
+    }
+
+    //the elements sharing a node with point, a rough neighbourhood rather than an exact hit test
+    pub fn query(&self, point: Vec2) -> Vec<Element<T>> {
+        let mut elements = Vec::new();
+        self.query_helper(ROOT, point, &mut elements);
+        return elements;
+    }
+
+    fn query_helper(&self, node: usize, point: Vec2, elements: &mut Vec<Element<T>>) {
+        let node = &self.nodes[node];
+        if !node.loose_bounds.contains(point) {
+            return;
+        }
+
+        elements.extend(node.elements.iter());
+        for child in node.children().into_iter().flatten() {
+            self.query_helper(child, point, elements);
+        }
+    }
+
+    //every element whose bounds touch rect, each reported once
+    pub fn query_area(&self, rect: &Rect) -> Vec<Element<T>> {
+        let mut elements = Vec::new();
+        self.query_area_helper(ROOT, rect, &mut elements);
+
+        let mut seen: HashSet<T> = HashSet::new();
+        elements.retain(|(item, _)| seen.insert(*item));
+        return elements;
+    }
+
+    fn query_area_helper(&self, node: usize, rect: &Rect, elements: &mut Vec<Element<T>>) {
+        let node = &self.nodes[node];
+        if !node.loose_bounds.intersects(rect) {
+            return;
+        }
+
+        elements.extend(node.elements.iter().filter(|(_, bounds)| bounds.intersects(rect)));
+        for child in node.children().into_iter().flatten() {
+            self.query_area_helper(child, rect, elements);
+        }
+    }
+
+    fn can_subdivide(&self, node: usize) -> bool {
+        let node = &self.nodes[node];
+        node.height < self.settings.max_depth && node.bounds.size.min_element() / 2. >= self.settings.min_node_size
+    }
+
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats { tracked: self.tracked.len(), ..Default::default() };
+        self.stats_helper(ROOT, &mut stats);
+        if stats.leaves > 0 {
+            stats.mean_leaf_elements = stats.stored as f32 / stats.leaves as f32;
+        }
+        return stats;
+    }
+
+    fn stats_helper(&self, node: usize, stats: &mut TreeStats) {
+        let node = &self.nodes[node];
+        stats.nodes += 1;
+        stats.stored += node.elements.len();
+        stats.deepest = stats.deepest.max(node.height);
+
+        if let Some(children) = node.children() {
+            for child in children {
+                self.stats_helper(child, stats);
+            }
+            return;
+        }
+
+        stats.leaves += 1;
+        stats.max_leaf_elements = stats.max_leaf_elements.max(node.elements.len());
+        if node.elements.is_empty() {
+            stats.empty_leaves += 1;
+        }
+        if node.elements.len() > self.settings.capacity {
+            stats.overfull_leaves += 1;
+        }
+    }
+
+    //hands out four consecutive nodes, recycled ones first
+    fn alloc_block(&mut self) -> usize {
+        if let Some(first) = self.free_blocks.pop() {
+            return first;
+        }
+
+        let first = self.nodes.len();
+        for _ in 0..4 {
+            self.nodes.push(Node::new(None, Rect::default(), &self.settings, 0));
+        }
+        return first;
+    }
+
+    pub fn subdivide(&mut self, node: usize) {
+        //println!("subdivide time!");
+        if !self.nodes[node].is_leaf() {
+            return;
+        }
+
+        let first = self.alloc_block();
+        let settings = self.settings;
+        let bounds = self.nodes[node].bounds;
+        let new_height = self.nodes[node].height + 1;
+        let new_size = bounds.size / 2.;
+
+        //top left, top right, bottom left, bottom right, the order child_of picks them in
+        let quarters = [
+            Rect::new(bounds.pos, new_size),
+            Rect::new(bounds.pos + Vec2::new(new_size.x, 0.), new_size),
+            Rect::new(bounds.pos + Vec2::new(0., -new_size.y), new_size),
+            Rect::new(bounds.pos + Vec2::new(new_size.x, -new_size.y), new_size),
+        ];
+        for (i, quarter) in quarters.iter().enumerate() {
+            self.nodes[first + i].reset(Some(node), *quarter, &settings, new_height);
+        }
+        self.nodes[node].first_child = Some(first);
+
+        //TAKE OLD ELEMENTS AND PUT THEM IN NEW TREES
+        let mut elements = std::mem::take(&mut self.nodes[node].elements);
+        for (item, rect) in elements.iter() {
+            self.insert_from(node, item, rect);
+        }
+
+        //give the buffer back unless loose elements too big for the children landed here again
+        if self.nodes[node].elements.is_empty() {
+            elements.clear();
+            self.nodes[node].elements = elements;
+        }
+    }
+
+    //empties the tree but keeps every node around for the next fill
+    pub fn clear(&mut self) {
+        self.tracked.clear();
+        for node in self.nodes.iter_mut() {
+            node.elements.clear();
+            node.first_child = None;
+        }
+
+        //lowest blocks get popped first so a rebuild fills the arena in the same order
+        self.free_blocks.clear();
+        self.free_blocks.extend((1..self.nodes.len()).step_by(4).rev());
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn trees() -> [QuadTree<u32>; 2] {
+        [
+            QuadTree::new(Vec2::new(0., 0.), Vec2::new(100., 100.), 4),
+            QuadTree::new_loose(Vec2::new(0., 0.), Vec2::new(100., 100.), 4, 2.),
+        ]
+    }
+
+    //every node currently holding item, freed nodes are always empty
+    fn holders(tree: &QuadTree<u32>, item: u32) -> Vec<usize> {
+        (0..tree.nodes.len()).filter(|node| tree.nodes[*node].elements.iter().any(|(e, _)| *e == item)).collect()
+    }
+
+    fn ids(elements: Vec<Element<u32>>) -> Vec<u32> {
+        let mut ids: Vec<u32> = elements.into_iter().map(|(e, _)| e).collect();
+        ids.sort();
+        return ids;
+    }
+
+    #[test]
+    fn update_moves_elements_across_nodes() {
+        for mut tree in trees() {
+            //two points per quarter splits the root
+            let points = [(10., -10.), (15., -30.), (90., -10.), (70., -30.), (10., -90.), (30., -70.), (90., -90.), (70., -70.)];
+            for (i, (x, y)) in points.iter().enumerate() {
+                tree.insert_point(&(i as u32), Vec2::new(*x, *y));
+            }
+            assert!(!tree.root().is_leaf());
+
+            let before = holders(&tree, 0);
+            assert_eq!(before.len(), 1);
+            assert!(tree.nodes[before[0]].bounds.contains(Vec2::new(10., -10.)));
+
+            let target = Vec2::new(85., -85.);
+            assert!(tree.update_point(&0, target));
+            let after = holders(&tree, 0);
+            assert_eq!(after.len(), 1);
+            assert_ne!(after, before);
+            assert!(tree.nodes[after[0]].bounds.contains(target));
+            assert_eq!(tree.tracked[&0], Rect::from_point(target));
+
+            assert!(ids(tree.query(target)).contains(&0));
+            assert!(!ids(tree.query(Vec2::new(10., -10.))).contains(&0));
+            assert_eq!(ids(tree.query_area(&Rect::new(Vec2::new(0., 0.), Vec2::new(50., 50.)))), vec![1]);
+
+            //a small move inside the same leaf leaves the element where it is
+            assert!(tree.update_point(&0, target + Vec2::new(1., -1.)));
+            assert_eq!(holders(&tree, 0), after);
+            assert_eq!(tree.stats().stored, points.len());
+        }
+    }
+
+    #[test]
+    fn removing_under_capacity_collapses() {
+        for mut tree in trees() {
+            //one point per quarter of the top left quarter, so it splits twice deep
+            let points = [(10., -10.), (40., -10.), (10., -40.), (40., -40.), (90., -90.)];
+            for (i, (x, y)) in points.iter().enumerate() {
+                tree.insert_point(&(i as u32), Vec2::new(*x, *y));
+            }
+            assert!(tree.stats().deepest >= 2);
+
+            //four left is still at capacity
+            tree.remove(&4);
+            assert!(!tree.root().is_leaf());
+
+            tree.remove(&3);
+            assert!(tree.root().is_leaf());
+            let stats = tree.stats();
+            assert_eq!((stats.nodes, stats.stored, stats.tracked), (1, 3, 3));
+            assert_eq!(ids(tree.query_area(tree.bounds())), vec![0, 1, 2]);
+
+            //splitting again reuses the freed nodes instead of growing the arena
+            let arena = tree.nodes.len();
+            tree.insert_point(&3, Vec2::new(40., -40.));
+            tree.insert_point(&4, Vec2::new(90., -90.));
+            assert!(!tree.root().is_leaf());
+            assert_eq!(tree.nodes.len(), arena);
+        }
+    }
+
+    #[test]
+    fn query_area_matches_brute_force() {
+        let size = Vec2::new(1000., 1000.);
+        let mut rng = StdRng::seed_from_u64(16);
+        let random_rect = |rng: &mut StdRng| {
+            let half_size = Vec2::new(rng.gen_range(0.0..15.), rng.gen_range(0.0..15.));
+            let center = Vec2::new(rng.gen_range(half_size.x..size.x - half_size.x), rng.gen_range(half_size.y - size.y..-half_size.y));
+            Rect::from_center(center, half_size)
+        };
+
+        let mut strict: QuadTree<u32> = QuadTree::new(Vec2::ZERO, size, 4);
+        let mut loose: QuadTree<u32> = QuadTree::new_loose(Vec2::ZERO, size, 4, 2.);
+        let mut expected: HashMap<u32, Rect> = HashMap::new();
+        for i in 0..300 {
+            let rect = random_rect(&mut rng);
+            strict.insert(&i, &rect);
+            loose.insert(&i, &rect);
+            expected.insert(i, rect);
+        }
+
+        //churn so the trees split, move and collapse before being checked
+        for _ in 0..600 {
+            let item = rng.gen_range(0..400);
+            if rng.gen_bool(0.2) {
+                strict.remove(&item);
+                loose.remove(&item);
+                expected.remove(&item);
+                continue;
+            }
+            let rect = if rng.gen_bool(0.5) {
+                random_rect(&mut rng)
+            } else {
+                //short moves mostly stay in their leaf
+                let Some(old) = expected.get(&item) else { continue };
+                let step = Vec2::new(rng.gen_range(-5.0..5.), rng.gen_range(-5.0..5.));
+                let center = (old.center() + step).clamp(old.half_size - Vec2::new(0., size.y), Vec2::new(size.x, 0.) - old.half_size);
+                Rect::from_center(center, old.half_size)
+            };
+            strict.update(&item, &rect);
+            loose.update(&item, &rect);
+            expected.insert(item, rect);
+        }
+
+        for _ in 0..100 {
+            let area = random_rect(&mut rng).grow(rng.gen_range(0.0..100.));
+            let mut brute: Vec<u32> = expected.iter().filter(|(_, rect)| rect.intersects(&area)).map(|(e, _)| *e).collect();
+            brute.sort();
+            assert_eq!(ids(strict.query_area(&area)), brute);
+            assert_eq!(ids(loose.query_area(&area)), brute);
+        }
+    }
+}
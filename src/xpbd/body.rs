@@ -0,0 +1,253 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use bevy::prelude::*;
+
+use crate::quadtree::Point;
+use super::{SolverConfig, EPSILON, GRAVITY};
+
+//velocity lost per second, small next to what friction takes out of anything resting on something
+pub const LINEAR_DAMPING: f32 = 0.1;
+//angular velocity lost per second, same idea as LINEAR_DAMPING
+pub const ANGULAR_DAMPING: f32 = 2.;
+
+//a point with orientation, the transform holds its position and rotation around z
+#[derive(Component)]
+pub struct RigidBody {
+    pub velo: Vec2,
+    pub angular_velo: f32,
+    //external force and torque, applied for one solver step and then cleared
+    pub force: Vec2,
+    pub torque: f32,
+    pub last_pos: Vec2,
+    pub last_rot: Quat,
+    mass: f32,
+    inv_mass: f32,
+    inertia: f32,
+    inv_inertia: f32,
+}
+
+impl RigidBody {
+    pub fn new(mass: f32, inertia: f32) -> Self {
+        let mut body = Self::new_static();
+        body.set_mass(mass);
+        body.set_inertia(inertia);
+        return body;
+    }
+
+    //infinite mass and inertia, constraints move everything else instead
+    pub fn new_static() -> Self {
+        Self {
+            velo: Vec2::ZERO,
+            angular_velo: 0.,
+            force: Vec2::ZERO,
+            torque: 0.,
+            last_pos: Vec2::ZERO,
+            last_rot: Quat::IDENTITY,
+            mass: f32::INFINITY,
+            inv_mass: 0.,
+            inertia: f32::INFINITY,
+            inv_inertia: 0.,
+        }
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn inv_mass(&self) -> f32 {
+        self.inv_mass
+    }
+
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    pub fn inv_inertia(&self) -> f32 {
+        self.inv_inertia
+    }
+
+    //zero, negative or infinite mass pins the body in place
+    pub fn set_mass(&mut self, mass: f32) {
+        let (mass, inv_mass) = inverse_pair(mass);
+        self.mass = mass;
+        self.inv_mass = inv_mass;
+    }
+
+    //zero, negative or infinite inertia stops the body rotating
+    pub fn set_inertia(&mut self, inertia: f32) {
+        let (inertia, inv_inertia) = inverse_pair(inertia);
+        self.inertia = inertia;
+        self.inv_inertia = inv_inertia;
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.inv_mass == 0. && self.inv_inertia == 0.
+    }
+
+    //velocity of the point r away from the center of mass
+    pub fn velocity_at(&self, r: Vec2) -> Vec2 {
+        self.velo + r.perp() * self.angular_velo
+    }
+}
+
+fn inverse_pair(value: f32) -> (f32, f32) {
+    if value > 0. && value.is_finite() {
+        return (value, 1. / value);
+    }
+    return (f32::INFINITY, 0.);
+}
+
+//what a constraint needs from either end, a Point is a body that can't rotate
+#[derive(Debug, Clone, Copy)]
+pub struct MassProps {
+    pub inv_mass: f32,
+    pub inv_inertia: f32,
+}
+
+impl MassProps {
+    pub fn of(point: Option<&Point>, body: Option<&RigidBody>) -> Self {
+        match (body, point) {
+            (Some(body), _) => Self { inv_mass: body.inv_mass, inv_inertia: body.inv_inertia },
+            (None, Some(point)) => Self { inv_mass: point.inv_mass, inv_inertia: 0. },
+            (None, None) => Self { inv_mass: 0., inv_inertia: 0. },
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.inv_mass == 0. && self.inv_inertia == 0.
+    }
+
+    //how easily a correction along n applied r away from the center moves this end
+    pub fn generalized_inverse_mass(&self, r: Vec2, n: Vec2) -> f32 {
+        let rn = r.perp_dot(n);
+        self.inv_mass + self.inv_inertia * rn * rn
+    }
+
+    //moves and turns the body by the positional impulse p applied r away from its center
+    pub fn apply_correction(&self, transform: &mut Transform, p: Vec2, r: Vec2) {
+        transform.translation += (p * self.inv_mass).extend(0.);
+        let angle = self.inv_inertia * r.perp_dot(p);
+        if angle != 0. {
+            rotate(transform, angle);
+        }
+    }
+}
+
+//the parts of either end of a constraint the solvers read and move
+pub type BodyParts = (&'static mut Transform, Option<&'static Point>, Option<&'static RigidBody>);
+//anything a constraint can be attached to
+pub type IsBody = Or<(With<Point>, With<RigidBody>)>;
+
+//renormalizes too, the many small turns of a solve otherwise drift the quaternion off unit length
+pub fn rotate(transform: &mut Transform, angle: f32) {
+    transform.rotation = (Quat::from_rotation_z(angle) * transform.rotation).normalize();
+}
+
+//one side of a contact while it's being solved
+pub struct SolverBody<'a> {
+    pub transform: &'a mut Transform,
+    pub mass: MassProps,
+    //pose at the start of the substep
+    pub last_pos: Vec2,
+    pub last_rot: Quat,
+}
+
+impl<'a> SolverBody<'a> {
+    pub fn new(transform: &'a mut Transform, point: Option<&Point>, body: Option<&RigidBody>) -> Self {
+        //anything without its own motion stayed where it is
+        let (last_pos, last_rot) = match (body, point) {
+            (Some(body), _) => (body.last_pos, body.last_rot),
+            (None, Some(point)) => (point.last_pos, Quat::IDENTITY),
+            (None, None) => (transform.translation.truncate(), transform.rotation),
+        };
+        Self {
+            transform: transform,
+            mass: MassProps::of(point, body),
+            last_pos: last_pos,
+            last_rot: last_rot,
+        }
+    }
+}
+
+//world space offset of a body local point
+pub fn world_offset(transform: &Transform, local: Vec2) -> Vec2 {
+    (transform.rotation * local.extend(0.)).truncate()
+}
+
+//rotation around z, in -PI..PI
+pub fn angle_of(rotation: Quat) -> f32 {
+    rotation.to_euler(EulerRot::ZYX).0
+}
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+pub fn integrate_bodies(
+    mut q_body: Query<(&mut RigidBody, &mut Transform)>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    for (mut body, mut transform) in q_body.iter_mut() {
+        let position = transform.translation.truncate();
+        body.last_pos = position;
+        body.last_rot = transform.rotation;
+
+        if body.inv_mass == 0. {
+            body.velo = Vec2::ZERO;
+        } else {
+            let accel = body.force * body.inv_mass + GRAVITY;
+            body.velo += accel * delta;
+            body.velo *= (1. - config.linear_damping * delta).max(0.);
+            transform.translation = (position + body.velo * delta).extend(transform.translation.z);
+        }
+
+        if body.inv_inertia == 0. {
+            body.angular_velo = 0.;
+        } else {
+            let angular_accel = body.torque * body.inv_inertia;
+            body.angular_velo += angular_accel * delta;
+            body.angular_velo *= (1. - config.angular_damping * delta).max(0.);
+            rotate(&mut transform, body.angular_velo * delta);
+        }
+    }
+}
+
+//like point accelerations, forces act through every substep of the step they were applied in
+pub fn clear_body_forces(mut q_body: Query<&mut RigidBody>) {
+    for mut body in q_body.iter_mut() {
+        body.force = Vec2::ZERO;
+        body.torque = 0.;
+    }
+}
+
+pub fn update_body_velocities(
+    mut q_body: Query<(&mut RigidBody, &Transform)>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    if delta < EPSILON {
+        return;
+    }
+    for (mut body, transform) in q_body.iter_mut() {
+        body.velo = (transform.translation.truncate() - body.last_pos) / delta;
+        //the rotation since the start of the substep, short way round
+        let turned = transform.rotation * body.last_rot.inverse();
+        body.angular_velo = angle_of(turned) / delta;
+    }
+}
+
+pub fn draw_bodies(
+    q_body: Query<(&RigidBody, &Transform)>,
+    mut gizmos: Gizmos,
+) {
+    //colliders draw the shape, this shows which way the body faces
+    for (body, transform) in q_body.iter() {
+        let pos = transform.translation.truncate();
+        let color = if body.is_static() { Color::GRAY } else { Color::GREEN };
+        gizmos.line_2d(pos, pos + world_offset(transform, Vec2::X * 10.), color);
+        gizmos.line_2d(pos, pos + world_offset(transform, Vec2::Y * 10.), color);
+    }
+}
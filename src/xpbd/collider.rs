@@ -0,0 +1,305 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::quadtree::{Rect, POINT_MASS, POINT_RADIUS};
+use super::{world_offset, RigidBody, EPSILON};
+
+//colliders made at this density weigh the same as a Point of the same area
+pub const DEFAULT_DENSITY: f32 = POINT_MASS / (PI * POINT_RADIUS * POINT_RADIUS);
+
+//shapes are in the body's local space, around its origin
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle {
+        radius: f32,
+    },
+    Box {
+        half_size: Vec2,
+    },
+    //a segment along local x rounded by radius, half_length is to the center of each cap
+    Capsule {
+        half_length: f32,
+        radius: f32,
+    },
+    //convex, counter clockwise, Collider::polygon builds it from any points
+    //hand built ones may be clockwise, an empty one never collides
+    Polygon {
+        vertices: Vec<Vec2>,
+    },
+    //no area and so no mass, meant for static level geometry
+    Segment {
+        a: Vec2,
+        b: Vec2,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MassProperties {
+    pub mass: f32,
+    //around the body's origin, which is also the centroid for every shape the constructors build
+    pub inertia: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Collider {
+    pub shape: Shape,
+    pub density: f32,
+}
+
+impl Collider {
+    pub fn new(shape: Shape) -> Self {
+        Self { shape: shape, density: DEFAULT_DENSITY }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self::new(Shape::Circle { radius: radius })
+    }
+
+    pub fn cuboid(half_size: Vec2) -> Self {
+        Self::new(Shape::Box { half_size: half_size })
+    }
+
+    pub fn capsule(half_length: f32, radius: f32) -> Self {
+        Self::new(Shape::Capsule { half_length: half_length, radius: radius })
+    }
+
+    //wraps the points in their convex hull, None if they don't enclose any area
+    //the hull is moved so its centroid sits on the body's origin, bodies spin around their origin
+    pub fn polygon(points: &[Vec2]) -> Option<Self> {
+        let mut vertices = convex_hull(points);
+        if vertices.len() < 3 {
+            return None;
+        }
+        let centroid = polygon_centroid(&vertices);
+        for vertex in vertices.iter_mut() {
+            *vertex -= centroid;
+        }
+        return Some(Self::new(Shape::Polygon { vertices: vertices }));
+    }
+
+    pub fn segment(a: Vec2, b: Vec2) -> Self {
+        Self::new(Shape::Segment { a: a, b: b })
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density.max(0.);
+        self
+    }
+
+    //what the broadphase stores for the collider at transform
+    pub fn aabb(&self, transform: &Transform) -> Rect {
+        let pos = transform.translation.truncate();
+        match &self.shape {
+            Shape::Circle { radius } => Rect::from_center(pos, Vec2::splat(*radius)),
+            Shape::Box { half_size } => {
+                //the box's half extents projected onto the world axes
+                let x = world_offset(transform, Vec2::new(half_size.x, 0.)).abs();
+                let y = world_offset(transform, Vec2::new(0., half_size.y)).abs();
+                Rect::from_center(pos, x + y)
+            }
+            Shape::Capsule { half_length, radius } => {
+                let axis = world_offset(transform, Vec2::new(*half_length, 0.)).abs();
+                Rect::from_center(pos, axis + Vec2::splat(*radius))
+            }
+            Shape::Polygon { vertices } if vertices.is_empty() => Rect::from_center(pos, Vec2::ZERO),
+            Shape::Polygon { vertices } => bounds_of(vertices.iter().map(|v| pos + world_offset(transform, *v))),
+            Shape::Segment { a, b } => bounds_of([*a, *b].into_iter().map(|v| pos + world_offset(transform, v))),
+        }
+    }
+
+    pub fn mass_properties(&self) -> MassProperties {
+        let density = self.density;
+        match &self.shape {
+            Shape::Circle { radius } => {
+                let mass = density * PI * radius * radius;
+                MassProperties { mass: mass, inertia: 0.5 * mass * radius * radius }
+            }
+            Shape::Box { half_size } => {
+                let mass = density * 4. * half_size.x * half_size.y;
+                MassProperties { mass: mass, inertia: mass * half_size.length_squared() / 3. }
+            }
+            Shape::Capsule { half_length, radius } => {
+                let (l, r) = (*half_length, *radius);
+                let rect_mass = density * 4. * l * r;
+                let rect_inertia = rect_mass * (l * l + r * r) / 3.;
+                //the caps make a full disc, each half sits offset by its centroid from the end of the segment
+                let cap_mass = density * PI * r * r;
+                let cap_offset = 4. * r / (3. * PI);
+                let cap_inertia = cap_mass * (0.5 * r * r + l * l + 2. * l * cap_offset);
+                MassProperties { mass: rect_mass + cap_mass, inertia: rect_inertia + cap_inertia }
+            }
+            Shape::Polygon { vertices } => {
+                //fan of triangles from the origin
+                let mut area = 0.;
+                let mut inertia = 0.;
+                for (i, a) in vertices.iter().enumerate() {
+                    let b = vertices[(i + 1) % vertices.len()];
+                    let cross = a.perp_dot(b);
+                    area += 0.5 * cross;
+                    inertia += cross * (a.dot(*a) + a.dot(b) + b.dot(b)) / 12.;
+                }
+                //a clockwise polygon sums up negative
+                MassProperties { mass: density * area.abs(), inertia: density * inertia.abs() }
+            }
+            Shape::Segment { .. } => MassProperties { mass: 0., inertia: 0. },
+        }
+    }
+}
+
+impl RigidBody {
+    //mass and inertia from the collider's area and density, shapes without area make a static body
+    pub fn from_collider(collider: &Collider) -> Self {
+        let props = collider.mass_properties();
+        return Self::new(props.mass, props.inertia);
+    }
+}
+
+fn bounds_of(points: impl Iterator<Item = Vec2>) -> Rect {
+    let (min, max) = points.fold((Vec2::MAX, Vec2::MIN), |(min, max), p| (min.min(p), max.max(p)));
+    Rect::from_center((min + max) * 0.5, (max - min) * 0.5)
+}
+
+//area weighted center of a counter clockwise polygon, the plain average for one without area
+pub fn polygon_centroid(vertices: &[Vec2]) -> Vec2 {
+    let mut area = 0.;
+    let mut centroid = Vec2::ZERO;
+    for (i, a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+        let cross = a.perp_dot(b);
+        area += 0.5 * cross;
+        centroid += (*a + b) * cross / 6.;
+    }
+    if area > EPSILON {
+        return centroid / area;
+    }
+    return vertices.iter().sum::<Vec2>() / vertices.len().max(1) as f32;
+}
+
+//monotone chain, counter clockwise with no collinear points
+pub fn convex_hull(points: &[Vec2]) -> Vec<Vec2> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut hull: Vec<Vec2> = Vec::with_capacity(sorted.len() * 2);
+    //lower half left to right, then the upper half back
+    for pass in [sorted.clone(), sorted.into_iter().rev().collect()] {
+        let start = hull.len();
+        for p in pass {
+            while hull.len() >= start + 2 && (hull[hull.len() - 1] - hull[hull.len() - 2]).perp_dot(p - hull[hull.len() - 2]) <= 0. {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        //the last point starts the other half
+        hull.pop();
+    }
+    return hull;
+}
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+pub fn draw_colliders(
+    q_collider: Query<(&Collider, &Transform)>,
+    mut gizmos: Gizmos,
+) {
+    for (collider, transform) in q_collider.iter() {
+        let pos = transform.translation.truncate();
+        let to_world = |v: Vec2| pos + world_offset(transform, v);
+        match &collider.shape {
+            Shape::Circle { radius } => {
+                gizmos.circle_2d(pos, *radius, Color::GREEN);
+                gizmos.line_2d(pos, to_world(Vec2::X * *radius), Color::GREEN);
+            }
+            Shape::Box { half_size } => {
+                let corners = [
+                    Vec2::new(-half_size.x, -half_size.y),
+                    Vec2::new(half_size.x, -half_size.y),
+                    Vec2::new(half_size.x, half_size.y),
+                    Vec2::new(-half_size.x, half_size.y),
+                    Vec2::new(-half_size.x, -half_size.y),
+                ];
+                gizmos.linestrip_2d(corners.map(to_world), Color::GREEN);
+            }
+            Shape::Capsule { half_length, radius } => {
+                let (a, b) = (to_world(Vec2::new(-half_length, 0.)), to_world(Vec2::new(*half_length, 0.)));
+                let side = world_offset(transform, Vec2::new(0., *radius));
+                gizmos.line_2d(a + side, b + side, Color::GREEN);
+                gizmos.line_2d(a - side, b - side, Color::GREEN);
+                gizmos.circle_2d(a, *radius, Color::GREEN);
+                gizmos.circle_2d(b, *radius, Color::GREEN);
+            }
+            Shape::Polygon { vertices } => {
+                let closed = vertices.iter().chain(vertices.first()).map(|v| to_world(*v));
+                gizmos.linestrip_2d(closed, Color::GREEN);
+            }
+            Shape::Segment { a, b } => gizmos.line_2d(to_world(*a), to_world(*b), Color::GRAY),
+        }
+    }
+}
+
+pub fn test_bodies(
+    mut commands: Commands
+) {
+    let mut spawn = |name: &str, collider: Collider, pos: Vec2, angle: f32, is_static: bool| {
+        let mut body = if is_static { RigidBody::new_static() } else { RigidBody::from_collider(&collider) };
+        let transform = Transform::from_translation(pos.extend(1.)).with_rotation(Quat::from_rotation_z(angle));
+        body.last_pos = pos;
+        body.last_rot = transform.rotation;
+        commands.spawn((body, collider, transform, Name::new(name.to_string())));
+    };
+
+    //a ramp with a wheel and a capsule to roll down it, a stack of crates and a rock at the bottom
+    spawn("ramp", Collider::segment(Vec2::new(-140., 0.), Vec2::new(140., 0.)), Vec2::new(200., -640.), -0.35, true);
+    spawn("wheel", Collider::circle(14.), Vec2::new(110., -580.), 0., false);
+    spawn("capsule", Collider::capsule(18., 7.), Vec2::new(170., -590.), -0.35, false);
+    for i in 0..4 {
+        spawn("crate", Collider::cuboid(Vec2::new(18., 14.)), Vec2::new(700., -785. + i as f32 * 28.5), 0., false);
+    }
+    let rock = Collider::polygon(&[
+        Vec2::new(-22., -12.),
+        Vec2::new(18., -14.),
+        Vec2::new(26., 6.),
+        Vec2::new(4., 18.),
+        Vec2::new(-18., 10.),
+    ]);
+    if let Some(rock) = rock {
+        spawn("rock", rock, Vec2::new(520., -770.), 0.4, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygons_are_centered_on_their_centroid() {
+        //an off center square weighs and spins like a box around its middle
+        let square = Collider::polygon(&[Vec2::new(10., 10.), Vec2::new(30., 10.), Vec2::new(30., 30.), Vec2::new(10., 30.)]).unwrap();
+        let Shape::Polygon { vertices } = &square.shape else {
+            panic!("expected a polygon");
+        };
+        assert!(vertices.iter().all(|v| (v.abs() - Vec2::splat(10.)).length() < 1e-4), "{vertices:?}");
+
+        let (props, expected) = (square.mass_properties(), Collider::cuboid(Vec2::splat(10.)).mass_properties());
+        assert!((props.mass - expected.mass).abs() < 1e-4);
+        assert!((props.inertia - expected.inertia).abs() < expected.inertia * 1e-4);
+
+        //and a lopsided hull ends up with its centroid on the origin
+        let rock = Collider::polygon(&[Vec2::new(-22., -12.), Vec2::new(18., -14.), Vec2::new(26., 6.), Vec2::new(4., 18.), Vec2::new(-18., 10.)]).unwrap();
+        let Shape::Polygon { vertices } = &rock.shape else {
+            panic!("expected a polygon");
+        };
+        assert!(polygon_centroid(vertices).length() < 1e-4);
+    }
+}
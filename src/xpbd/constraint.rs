@@ -0,0 +1,200 @@
+#![allow(clippy::redundant_field_names)]
+
+use bevy::prelude::*;
+
+use crate::quadtree::Point;
+use super::{delta_lambda, world_offset, BodyParts, Collider, IsBody, MassProps, RigidBody, SolverConfig, EPSILON};
+
+//keeps two points rest_length apart, compliance 0 is a rigid link
+//either end can be a rigid body, held at an anchor in its local space
+#[derive(Component)]
+pub struct DistanceConstraint {
+    pub a: Entity,
+    pub b: Entity,
+    pub anchor_a: Vec2,
+    pub anchor_b: Vec2,
+    pub rest_length: f32,
+    pub compliance: f32,
+    pub(super) lambda: f32,
+}
+
+impl DistanceConstraint {
+    pub fn new(a: Entity, b: Entity, rest_length: f32, compliance: f32) -> Self {
+        Self::with_anchors(a, Vec2::ZERO, b, Vec2::ZERO, rest_length, compliance)
+    }
+
+    //rest_length 0 pins the anchors together, a hinge between two bodies
+    pub fn with_anchors(a: Entity, anchor_a: Vec2, b: Entity, anchor_b: Vec2, rest_length: f32, compliance: f32) -> Self {
+        Self {
+            a: a,
+            b: b,
+            anchor_a: anchor_a,
+            anchor_b: anchor_b,
+            rest_length: rest_length,
+            compliance: compliance,
+            lambda: 0.,
+        }
+    }
+
+    pub fn lambda(&self) -> f32 {
+        self.lambda
+    }
+}
+
+pub struct RopeSettings {
+    //number of links, the rope has segments + 1 points
+    pub segments: usize,
+    pub radius: f32,
+    pub compliance: f32,
+    pub pin_start: bool,
+    pub pin_end: bool,
+}
+
+impl Default for RopeSettings {
+    fn default() -> Self {
+        Self {
+            segments: 10,
+            radius: 5.,
+            compliance: 0.,
+            pin_start: true,
+            pin_end: false,
+        }
+    }
+}
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+pub fn link_points(
+    commands: &mut Commands,
+    a: Entity,
+    b: Entity,
+    rest_length: f32,
+    compliance: f32,
+) -> Entity {
+    commands.spawn((
+        DistanceConstraint::new(a, b, rest_length, compliance),
+        Name::new("distance constraint"),
+    )).id()
+}
+
+//spawns the points of a rope from start to end and links them in order, returns the points
+pub fn spawn_rope(
+    commands: &mut Commands,
+    start: Vec2,
+    end: Vec2,
+    settings: &RopeSettings,
+) -> Vec<Entity> {
+    let segments = settings.segments.max(1);
+    let rest_length = start.distance(end) / segments as f32;
+    let mut points = Vec::with_capacity(segments + 1);
+
+    for i in 0..=segments {
+        let pos = start.lerp(end, i as f32 / segments as f32);
+        let mut point = Point::with_radius(Vec2::ZERO, settings.radius);
+        point.last_pos = pos;
+        if (i == 0 && settings.pin_start) || (i == segments && settings.pin_end) {
+            point.inv_mass = 0.;
+        }
+
+        let ent = commands.spawn((
+            point,
+            Transform::from_translation(pos.extend(1.)),
+            Name::new(format!("rope point {i}")),
+        )).id();
+
+        if let Some(last) = points.last() {
+            link_points(commands, *last, ent, rest_length, settings.compliance);
+        }
+        points.push(ent);
+    }
+    points
+}
+
+pub fn solve_distance_constraints(
+    mut q_constraint: Query<&mut DistanceConstraint>,
+    mut q_body: Query<BodyParts, IsBody>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    for mut constraint in q_constraint.iter_mut() {
+        let Ok([(mut trans_a, point_a, body_a), (mut trans_b, point_b, body_b)]) = q_body.get_many_mut([constraint.a, constraint.b]) else {
+            continue;
+        };
+        let mass_a = MassProps::of(point_a, body_a);
+        let mass_b = MassProps::of(point_b, body_b);
+
+        let r_a = world_offset(&trans_a, constraint.anchor_a);
+        let r_b = world_offset(&trans_b, constraint.anchor_b);
+        let diff = (trans_a.translation.truncate() + r_a) - (trans_b.translation.truncate() + r_b);
+        let dist = diff.length();
+        if dist < EPSILON {
+            continue;
+        }
+
+        let normal = diff / dist;
+        let w_sum = mass_a.generalized_inverse_mass(r_a, normal) + mass_b.generalized_inverse_mass(r_b, normal);
+        if w_sum < EPSILON {
+            continue;
+        }
+
+        let c = dist - constraint.rest_length;
+        let d_lambda = delta_lambda(c, w_sum, constraint.lambda, constraint.compliance, delta);
+        constraint.lambda += d_lambda;
+
+        let impulse = normal * d_lambda;
+        mass_a.apply_correction(&mut trans_a, impulse, r_a);
+        mass_b.apply_correction(&mut trans_b, -impulse, r_b);
+    }
+}
+
+pub fn draw_distance_constraints(
+    q_constraint: Query<&DistanceConstraint>,
+    q_body: Query<&Transform, IsBody>,
+    mut gizmos: Gizmos,
+) {
+    for constraint in q_constraint.iter() {
+        let Ok([trans_a, trans_b]) = q_body.get_many([constraint.a, constraint.b]) else {
+            continue;
+        };
+        let start = trans_a.translation.truncate() + world_offset(trans_a, constraint.anchor_a);
+        let end = trans_b.translation.truncate() + world_offset(trans_b, constraint.anchor_b);
+        gizmos.line_2d(start, end, Color::WHITE);
+    }
+}
+
+pub fn test_rope(
+    mut commands: Commands
+) {
+    //a bridge pinned at both ends and a pendulum hanging from its middle
+    let bridge = spawn_rope(&mut commands, Vec2::new(150., -200.), Vec2::new(650., -200.), &RopeSettings {
+        segments: 25,
+        pin_end: true,
+        ..default()
+    });
+    let pendulum = spawn_rope(&mut commands, Vec2::new(390., -200.), Vec2::new(390., -350.), &RopeSettings {
+        segments: 6,
+        pin_start: false,
+        ..default()
+    });
+    link_points(&mut commands, bridge[12], pendulum[0], 0., 0.);
+
+    //a crate hung by one corner from the pendulum, it swings and turns
+    let half_size = Vec2::new(20., 12.);
+    let collider = Collider::cuboid(half_size);
+    let mut body = RigidBody::from_collider(&collider);
+    body.last_pos = Vec2::new(420., -380.);
+    let crate_ent = commands.spawn((
+        body,
+        collider,
+        Transform::from_translation(Vec3::new(420., -380., 1.)),
+        Name::new("crate"),
+    )).id();
+    commands.spawn((
+        DistanceConstraint::with_anchors(pendulum[6], Vec2::ZERO, crate_ent, Vec2::new(-half_size.x, half_size.y), 0., 0.),
+        Name::new("crate hinge"),
+    ));
+}
@@ -0,0 +1,285 @@
+#![allow(clippy::needless_return)]
+
+use bevy::prelude::*;
+
+use crate::quadtree::Point;
+use super::{world_offset, ContactPoint, Contacts, MassProps, RigidBody, SolverBody, SolverConfig, EPSILON};
+
+/*
+    ---
+    SOURCES
+    https://matthias-research.github.io/pages/publications/PBDBodies.pdf
+    ---
+*/
+
+//surface properties, anything without one uses the default
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsMaterial {
+    //how hard the surfaces grip before they start sliding, relative to the normal force
+    pub static_friction: f32,
+    //how hard they drag on each other once sliding
+    pub dynamic_friction: f32,
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self {
+            static_friction: 0.6,
+            dynamic_friction: 0.4,
+        }
+    }
+}
+
+impl PhysicsMaterial {
+    pub fn new(static_friction: f32, dynamic_friction: f32) -> Self {
+        Self { static_friction: static_friction.max(0.), dynamic_friction: dynamic_friction.max(0.) }
+    }
+
+    pub fn frictionless() -> Self {
+        Self::new(0., 0.)
+    }
+}
+
+//how the coefficients of two touching materials make the contact's
+#[derive(Reflect, Debug, Clone, Copy, Default, PartialEq)]
+pub enum CombineRule {
+    Average,
+    Min,
+    Max,
+    Multiply,
+    #[default]
+    GeometricMean,
+}
+
+impl CombineRule {
+    pub fn combine(&self, a: f32, b: f32) -> f32 {
+        match self {
+            CombineRule::Average => (a + b) * 0.5,
+            CombineRule::Min => a.min(b),
+            CombineRule::Max => a.max(b),
+            CombineRule::Multiply => a * b,
+            CombineRule::GeometricMean => (a * b).sqrt(),
+        }
+    }
+}
+
+//the combined coefficients of one contact
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Friction {
+    pub static_coefficient: f32,
+    pub dynamic_coefficient: f32,
+}
+
+impl Friction {
+    pub fn new(a: &PhysicsMaterial, b: &PhysicsMaterial, rule: CombineRule) -> Self {
+        Self {
+            static_coefficient: rule.combine(a.static_friction, b.static_friction),
+            dynamic_coefficient: rule.combine(a.dynamic_friction, b.dynamic_friction),
+        }
+    }
+}
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+//undoes the sliding of a contact point over the substep, as long as the normal force can hold it
+//returns the change in the tangential multiplier, 0 once it slides and dynamic friction takes over
+pub fn solve_static_friction(
+    point: &ContactPoint,
+    normal: Vec2,
+    a: &mut SolverBody,
+    b: &mut SolverBody,
+    normal_lambda: f32,
+    tangent_lambda: f32,
+    coefficient: f32,
+) -> f32 {
+    if normal_lambda <= 0. {
+        return 0.;
+    }
+
+    //how far the two points moved against each other this substep
+    let r_a = world_offset(a.transform, point.local_a);
+    let r_b = world_offset(b.transform, point.local_b);
+    let moved_a = a.transform.translation.truncate() + r_a - (a.last_pos + (a.last_rot * point.local_a.extend(0.)).truncate());
+    let moved_b = b.transform.translation.truncate() + r_b - (b.last_pos + (b.last_rot * point.local_b.extend(0.)).truncate());
+    let moved = moved_a - moved_b;
+    let slide = moved - normal * moved.dot(normal);
+    let distance = slide.length();
+    if distance < EPSILON {
+        return 0.;
+    }
+
+    let tangent = slide / distance;
+    let w_sum = a.mass.generalized_inverse_mass(r_a, tangent) + b.mass.generalized_inverse_mass(r_b, tangent);
+    if w_sum < EPSILON {
+        return 0.;
+    }
+    let d_lambda = distance / w_sum;
+    if tangent_lambda + d_lambda > coefficient * normal_lambda {
+        return 0.;
+    }
+
+    let impulse = tangent * d_lambda;
+    a.mass.apply_correction(a.transform, -impulse, r_a);
+    b.mass.apply_correction(b.transform, impulse, r_b);
+    return d_lambda;
+}
+
+fn velocity_at(point: &Option<Mut<Point>>, body: &Option<Mut<RigidBody>>, r: Vec2) -> Vec2 {
+    match (body, point) {
+        (Some(body), _) => body.velocity_at(r),
+        (None, Some(point)) => point.velo,
+        (None, None) => Vec2::ZERO,
+    }
+}
+
+fn apply_impulse(point: &mut Option<Mut<Point>>, body: &mut Option<Mut<RigidBody>>, impulse: Vec2, r: Vec2) {
+    match (body, point) {
+        (Some(body), _) => {
+            let (inv_mass, inv_inertia) = (body.inv_mass(), body.inv_inertia());
+            body.velo += impulse * inv_mass;
+            body.angular_velo += inv_inertia * r.perp_dot(impulse);
+        }
+        (None, Some(point)) => {
+            let inv_mass = point.inv_mass;
+            point.velo += impulse * inv_mass;
+        }
+        (None, None) => {}
+    }
+}
+
+//velocity pass after each substep, sliding contacts lose tangential speed in proportion to how hard they were pressed together
+pub fn solve_dynamic_friction(
+    mut q_body: Query<(&Transform, Option<&mut Point>, Option<&mut RigidBody>)>,
+    contacts: Res<Contacts>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    if delta < EPSILON {
+        return;
+    }
+
+    for contact in contacts.0.iter() {
+        let normal = contact.manifold.normal;
+        let coefficient = contact.friction.dynamic_coefficient;
+        if coefficient <= 0. {
+            continue;
+        }
+
+        //the world bounds are static, so only a's side of a wall contact moves
+        let (trans_a, mut point_a, mut body_a, trans_b, mut point_b, mut body_b) = match contact.b {
+            Some(b) => {
+                let Ok([(trans_a, point_a, body_a), (trans_b, point_b, body_b)]) = q_body.get_many_mut([contact.a, b]) else {
+                    continue;
+                };
+                (*trans_a, point_a, body_a, *trans_b, point_b, body_b)
+            }
+            None => {
+                let Ok((trans_a, point_a, body_a)) = q_body.get_mut(contact.a) else {
+                    continue;
+                };
+                (*trans_a, point_a, body_a, Transform::IDENTITY, None, None)
+            }
+        };
+        let mass_a = MassProps::of(point_a.as_deref(), body_a.as_deref());
+        let mass_b = MassProps::of(point_b.as_deref(), body_b.as_deref());
+
+        for (i, point) in contact.manifold.points().iter().enumerate() {
+            let normal_lambda = contact.lambda[i];
+            if normal_lambda <= 0. {
+                continue;
+            }
+
+            let r_a = world_offset(&trans_a, point.local_a);
+            let r_b = world_offset(&trans_b, point.local_b);
+            let velo = velocity_at(&point_a, &body_a, r_a) - velocity_at(&point_b, &body_b, r_b);
+            let sliding = velo - normal * velo.dot(normal);
+            let speed = sliding.length();
+            if speed < EPSILON {
+                continue;
+            }
+
+            let tangent = sliding / speed;
+            let w_sum = mass_a.generalized_inverse_mass(r_a, tangent) + mass_b.generalized_inverse_mass(r_b, tangent);
+            if w_sum < EPSILON {
+                continue;
+            }
+
+            //the normal impulse over the substep is lambda / dt, friction takes at most a share of it and never reverses the slide
+            let strength = (coefficient * normal_lambda / delta).min(speed / w_sum);
+            let impulse = -tangent * strength;
+            apply_impulse(&mut point_a, &mut body_a, impulse, r_a);
+            apply_impulse(&mut point_b, &mut body_b, -impulse, r_b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::broadphase::{BroadphaseKind, EntityBroadphase};
+    use crate::quadtree::Rect;
+    use super::super::{Collider, XpbdPlugin, GRAVITY};
+    use super::*;
+
+    const SLOPE: f32 = 0.4;
+
+    //how far a crate set down on a slope with both surfaces made of material slides along it in three seconds
+    fn slide_down_slope(material: PhysicsMaterial, config: SolverConfig) -> f32 {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(XpbdPlugin);
+        app.insert_resource(EntityBroadphase::new(Rect::new(Vec2::ZERO, Vec2::new(1000., 800.)), BroadphaseKind::HashGrid { cell_size: 50. }));
+        app.insert_resource(config);
+
+        let rotation = Quat::from_rotation_z(SLOPE);
+        let center = Vec2::new(500., -400.);
+        let on_slope = center + (rotation * Vec3::new(0., 20., 0.)).truncate();
+        for (body, collider, pos) in [
+            (RigidBody::new_static(), Collider::cuboid(Vec2::new(200., 10.)), center),
+            (RigidBody::from_collider(&Collider::cuboid(Vec2::splat(10.))), Collider::cuboid(Vec2::splat(10.)), on_slope),
+        ] {
+            let mut body = body;
+            body.last_pos = pos;
+            body.last_rot = rotation;
+            app.world.spawn((body, collider, material, Transform::from_translation(pos.extend(1.)).with_rotation(rotation)));
+        }
+
+        for _ in 0..192 {
+            app.update();
+            app.world.run_schedule(FixedUpdate);
+        }
+        let mut q_body = app.world.query::<(&RigidBody, &Transform)>();
+        let (_, transform) = q_body.iter(&app.world).find(|(body, _)| body.inv_mass() > 0.).unwrap();
+        let downhill = (rotation * Vec3::NEG_X).truncate();
+        return (transform.translation.truncate() - on_slope).dot(downhill);
+    }
+
+    //the defaults, and damping off so only friction holds the crate
+    fn configs() -> [SolverConfig; 2] {
+        [SolverConfig::default(), SolverConfig { linear_damping: 0., angular_damping: 0., ..default() }]
+    }
+
+    #[test]
+    fn sticks_when_static_friction_beats_the_slope() {
+        assert!(SLOPE.tan() < 0.6);
+        for config in configs() {
+            let damping = config.linear_damping;
+            let slid = slide_down_slope(PhysicsMaterial::new(0.6, 0.5), config);
+            assert!(slid.abs() < 0.5, "damping {damping}: slid {slid}");
+        }
+    }
+
+    #[test]
+    fn slides_when_the_slope_beats_static_friction() {
+        assert!(SLOPE.tan() > 0.2);
+        for config in configs() {
+            let damping = config.linear_damping;
+            let slid = slide_down_slope(PhysicsMaterial::new(0.2, 0.1), config);
+            //a = g (sin - mu cos), a good part of the frictionless 0.5 a t^2
+            let expected = 0.5 * -GRAVITY.y * (SLOPE.sin() - 0.1 * SLOPE.cos()) * 9.;
+            assert!(slid > expected * 0.5, "damping {damping}: slid {slid}, expected about {expected}");
+        }
+    }
+}
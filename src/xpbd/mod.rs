@@ -0,0 +1,571 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::borrow::Cow;
+
+use bevy::{prelude::*, ecs::schedule::ScheduleLabel, utils::HashSet};
+
+use crate::broadphase::{send_pair_events, switch_broadphase, BroadphasePairs, EntityBroadphase, PairEvent};
+use crate::quadtree::{Point, Rect};
+
+pub mod body;
+pub mod collider;
+pub mod constraint;
+pub mod friction;
+pub mod narrowphase;
+pub use body::*;
+pub use collider::*;
+pub use constraint::*;
+pub use friction::*;
+pub use narrowphase::*;
+
+/*
+    ---
+    SOURCES
+    https://matthias-research.github.io/pages/publications/XPBD.pdf
+    https://matthias-research.github.io/pages/publications/smallsteps.pdf
+    ---
+*/
+
+pub const GRAVITY: Vec2 = Vec2::new(0., -370.);
+
+//velocity points lose per second, replaces the old verlet damping
+pub const POINT_DAMPING: f32 = 40.;
+
+const CONTACT_COMPLIANCE: f32 = 0.0;
+//contacts are kept up to this far apart, so resting things don't drop in and out of touching
+const CONTACT_MARGIN: f32 = 1.;
+const EPSILON: f32 = 0.0001;
+
+pub struct XpbdPlugin;
+
+impl Plugin for XpbdPlugin {
+    fn build(&self, app: &mut App) {
+        let mut substeps = Schedule::new(SubstepSchedule);
+        substeps
+            .configure_sets((
+                SubstepSet::Integrate,
+                SubstepSet::Solve,
+                SubstepSet::UpdateVelocities,
+            ).chain())
+            .add_systems((
+                (integrate_points, integrate_bodies).in_set(SubstepSet::Integrate),
+                (reset_multipliers, run_iterations).chain().in_set(SubstepSet::Solve),
+                ((update_velocities, update_body_velocities), solve_dynamic_friction).chain().in_set(SubstepSet::UpdateVelocities),
+            ));
+
+        //constraints and contacts take turns every iteration, so neither gets the last word
+        let mut iterations = Schedule::new(IterationSchedule);
+        iterations.add_systems((solve_distance_constraints, solve_contacts, solve_bounds.run_if(resource_exists::<EntityBroadphase>())).chain());
+
+        app
+            .init_resource::<SolverConfig>()
+            .init_resource::<Contacts>()
+            .init_resource::<BroadphasePairs>()
+            .add_event::<PairEvent<Entity>>()
+            .register_type::<SolverConfig>()
+            .register_type::<PhysicsMaterial>()
+            .add_schedule(substeps)
+            .add_schedule(iterations)
+            .configure_sets(FixedUpdate, (
+                XpbdSet::Prepare,
+                XpbdSet::Substeps,
+            ).chain())
+            .add_systems(First, (sync_fixed_timestep, switch_broadphase))
+            //nothing collides until the app inserts an EntityBroadphase
+            .add_systems(PostUpdate, remove_despawned.run_if(resource_exists::<EntityBroadphase>()))
+            .add_systems(FixedUpdate, (
+                (update_broadphase, send_pair_events, collect_pairs, collect_contacts, collect_bound_contacts).chain()
+                    .run_if(resource_exists::<EntityBroadphase>())
+                    .in_set(XpbdSet::Prepare),
+                (run_substeps, (clear_point_accels, clear_body_forces)).chain().in_set(XpbdSet::Substeps),
+            ));
+    }
+}
+
+/*
+    -------------------------------------
+        SETS / RESOURCES
+    -------------------------------------
+*/
+
+//ordered stages of one solver step, gameplay systems can run .before() / .after() any of them
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XpbdSet {
+    //broadphase, gathers the contacts solved this step
+    Prepare,
+    //runs SubstepSchedule SolverConfig::substeps times
+    Substeps,
+}
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubstepSchedule;
+
+//one pass over every constraint, run SolverConfig::iterations times inside SubstepSet::Solve
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IterationSchedule;
+
+//ordered stages of a single substep inside SubstepSchedule
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubstepSet {
+    //predicts positions from velocity and external forces
+    Integrate,
+    //projects every constraint onto the predicted positions, see IterationSchedule
+    Solve,
+    //derives the new velocities from the corrected positions
+    UpdateVelocities,
+}
+
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct SolverConfig {
+    //fixed step of the whole solve, also drives the FixedUpdate timestep
+    pub dt: f32,
+    pub substeps: usize,
+    pub iterations: usize,
+    //how two materials' friction mixes at a contact
+    pub friction_combine: CombineRule,
+    //velocity lost per second, points are tuned heavy so the flock settles
+    pub point_damping: f32,
+    //rigid bodies only lose a little, so contact friction is what stops them
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            dt: 1. / 64.,
+            substeps: 4,
+            iterations: 2,
+            friction_combine: CombineRule::default(),
+            point_damping: POINT_DAMPING,
+            linear_damping: LINEAR_DAMPING,
+            angular_damping: ANGULAR_DAMPING,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn substep_dt(&self) -> f32 {
+        self.dt / self.substeps.max(1) as f32
+    }
+}
+
+pub struct Contact {
+    pub a: Entity,
+    //None is a wall of the world bounds, its side of the manifold is in world space
+    pub b: Option<Entity>,
+    pub manifold: Manifold,
+    pub friction: Friction,
+    pub compliance: f32,
+    //normal and tangential multipliers, one per manifold point
+    pub lambda: [f32; 2],
+    pub tangent_lambda: [f32; 2],
+}
+
+impl Contact {
+    pub fn new(a: Entity, b: Option<Entity>, manifold: Manifold, friction: Friction) -> Self {
+        Self {
+            a: a,
+            b: b,
+            manifold: manifold,
+            friction: friction,
+            compliance: CONTACT_COMPLIANCE,
+            lambda: [0.; 2],
+            tangent_lambda: [0.; 2],
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Contacts(pub Vec<Contact>);
+
+//what the narrowphase needs to know about either side of a pair
+type ShapeParts = (&'static Transform, Option<&'static Collider>, Option<&'static Point>, Option<&'static RigidBody>, Option<&'static PhysicsMaterial>);
+//points with a collider are kept in the broadphase by its shape instead
+type MovedPoint = (Changed<Transform>, Without<Collider>);
+type MovedCollider = Or<(Changed<Transform>, Changed<Collider>)>;
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+//XPBD multiplier update for one constraint: C(x), sum of inverse masses, accumulated lambda, compliance, step
+pub fn delta_lambda(c: f32, w_sum: f32, lambda: f32, compliance: f32, dt: f32) -> f32 {
+    let alpha = compliance / (dt * dt);
+    let denom = w_sum + alpha;
+    if denom < EPSILON {
+        return 0.;
+    }
+    (-c - alpha * lambda) / denom
+}
+
+fn sync_fixed_timestep(
+    config: Res<SolverConfig>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    if config.is_changed() && config.dt > EPSILON {
+        fixed_time.set_timestep_seconds(config.dt as f64);
+    }
+}
+
+fn run_substeps(world: &mut World) {
+    let substeps = world.resource::<SolverConfig>().substeps;
+    for _ in 0..substeps {
+        world.run_schedule(SubstepSchedule);
+    }
+}
+
+//external accelerations last the whole step, so every substep integrates them and the step count doesn't change the motion
+fn clear_point_accels(mut q_point: Query<&mut Point>) {
+    for mut point in q_point.iter_mut() {
+        point.accel = Vec2::ZERO;
+    }
+}
+
+//multipliers only accumulate within a substep
+fn reset_multipliers(
+    mut q_constraint: Query<&mut DistanceConstraint>,
+    mut contacts: ResMut<Contacts>,
+) {
+    for mut constraint in q_constraint.iter_mut() {
+        constraint.lambda = 0.;
+    }
+    for contact in contacts.0.iter_mut() {
+        contact.lambda = [0.; 2];
+        contact.tangent_lambda = [0.; 2];
+    }
+}
+
+fn run_iterations(world: &mut World) {
+    let iterations = world.resource::<SolverConfig>().iterations;
+    for _ in 0..iterations {
+        world.run_schedule(IterationSchedule);
+    }
+}
+
+fn update_broadphase(
+    q_point: Query<(Entity, &Point, &Transform), MovedPoint>,
+    q_collider: Query<(Entity, &Collider, &Transform), MovedCollider>,
+    mut broadphase: ResMut<EntityBroadphase>,
+) {
+    for (ent, point, transform) in q_point.iter() {
+        broadphase.update(&ent, &point.bounds(transform.translation.truncate()));
+    }
+    //a collider's shape wins over a point's radius
+    for (ent, collider, transform) in q_collider.iter() {
+        broadphase.update_collider(&ent, &collider.aabb(transform));
+    }
+}
+
+//runs every frame, FixedUpdate can skip frames and miss the removal events
+fn remove_despawned(
+    mut removed_points: RemovedComponents<Point>,
+    mut removed_colliders: RemovedComponents<Collider>,
+    q_point: Query<(&Point, &Transform)>,
+    mut broadphase: ResMut<EntityBroadphase>,
+) {
+    for ent in removed_points.read() {
+        broadphase.remove(&ent);
+    }
+    //a point that only lost its collider goes back to being tracked by radius
+    for ent in removed_colliders.read() {
+        match q_point.get(ent) {
+            Ok((point, transform)) => broadphase.update(&ent, &point.bounds(transform.translation.truncate())),
+            Err(_) => broadphase.remove(&ent),
+        };
+    }
+}
+
+fn speed(point: Option<&Point>, body: Option<&RigidBody>) -> f32 {
+    match (body, point) {
+        (Some(body), _) => body.velo.length(),
+        (None, Some(point)) => point.velo.length(),
+        (None, None) => 0.,
+    }
+}
+
+//what the narrowphase sees, a point without a collider is a circle of its radius
+fn shape_of<'a>(collider: Option<&'a Collider>, point: Option<&Point>) -> Option<Cow<'a, Collider>> {
+    match (collider, point) {
+        (Some(collider), _) => Some(Cow::Borrowed(collider)),
+        (None, Some(point)) => Some(Cow::Owned(Collider::circle(point.radius))),
+        (None, None) => None,
+    }
+}
+
+fn collect_pairs(
+    q_motion: Query<(Option<&Point>, Option<&RigidBody>)>,
+    broadphase: Res<EntityBroadphase>,
+    mut pairs: ResMut<BroadphasePairs>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.dt;
+    //things can close the gap while integrating, so look a step ahead
+    pairs.0 = broadphase.pairs(&|ent| q_motion.get(*ent).map_or(0., |(point, body)| speed(point, body) * delta) + CONTACT_MARGIN * 0.5);
+}
+
+//narrowphase, turns the broadphase pairs into the contacts solved this step
+fn collect_contacts(
+    q_shape: Query<ShapeParts>,
+    q_constraint: Query<&DistanceConstraint>,
+    pairs: Res<BroadphasePairs>,
+    mut contacts: ResMut<Contacts>,
+    config: Res<SolverConfig>,
+) {
+    contacts.0.clear();
+    let delta = config.dt;
+
+    //linked points would fight their own constraint
+    let linked: HashSet<(Entity, Entity)> = q_constraint.iter()
+        .flat_map(|c| [(c.a, c.b), (c.b, c.a)])
+        .collect();
+
+    for (a, b) in pairs.0.iter() {
+        if linked.contains(&(*a, *b)) {
+            continue;
+        }
+        let Ok([(trans_a, collider_a, point_a, body_a, material_a), (trans_b, collider_b, point_b, body_b, material_b)]) = q_shape.get_many([*a, *b]) else {
+            continue;
+        };
+        //two static things can't move each other
+        if MassProps::of(point_a, body_a).is_static() && MassProps::of(point_b, body_b).is_static() {
+            continue;
+        }
+        let (Some(shape_a), Some(shape_b)) = (shape_of(collider_a, point_a), shape_of(collider_b, point_b)) else {
+            continue;
+        };
+
+        let margin = (speed(point_a, body_a) + speed(point_b, body_b)) * delta + CONTACT_MARGIN;
+        if let Some(manifold) = collide(&shape_a, trans_a, &shape_b, trans_b, margin) {
+            let friction = Friction::new(&material_a.copied().unwrap_or_default(), &material_b.copied().unwrap_or_default(), config.friction_combine);
+            contacts.0.push(Contact::new(*a, Some(*b), manifold, friction));
+        }
+    }
+}
+
+//contacts with the walls of the world bounds, which are thick boxes outside of it so nothing gets pushed out through them
+fn collect_bound_contacts(
+    q_shape: Query<(Entity, ShapeParts)>,
+    broadphase: Res<EntityBroadphase>,
+    mut contacts: ResMut<Contacts>,
+    config: Res<SolverConfig>,
+) {
+    let bounds = broadphase.bounds;
+    let thickness = bounds.size.max_element();
+    let walls = [
+        Rect::new(Vec2::new(bounds.left - thickness, bounds.bottom), Vec2::new(bounds.size.x + thickness * 2., thickness)),
+        Rect::new(Vec2::new(bounds.left - thickness, bounds.top + thickness), Vec2::new(bounds.size.x + thickness * 2., thickness)),
+        Rect::new(Vec2::new(bounds.left - thickness, bounds.top), Vec2::new(thickness, bounds.size.y)),
+        Rect::new(Vec2::new(bounds.right, bounds.top), Vec2::new(thickness, bounds.size.y)),
+    ];
+    //in world space, so the wall's side of a contact needs no transform
+    //built by hand since Collider::polygon would move them onto the origin
+    let walls = walls.map(|wall| Collider::new(Shape::Polygon { vertices: vec![
+        Vec2::new(wall.left, wall.bottom),
+        Vec2::new(wall.right, wall.bottom),
+        Vec2::new(wall.right, wall.top),
+        Vec2::new(wall.left, wall.top),
+    ] }));
+    let wall_material = PhysicsMaterial::default();
+    let delta = config.dt;
+
+    for (ent, (transform, collider, point, body, material)) in q_shape.iter() {
+        if MassProps::of(point, body).is_static() {
+            continue;
+        }
+        let Some(shape) = shape_of(collider, point) else {
+            continue;
+        };
+
+        let margin = speed(point, body) * delta + CONTACT_MARGIN;
+        if bounds.encloses(&shape.aabb(transform).grow(margin)) {
+            continue;
+        }
+        let friction = Friction::new(&material.copied().unwrap_or_default(), &wall_material, config.friction_combine);
+        for wall in walls.iter() {
+            if let Some(manifold) = collide(&shape, transform, wall, &Transform::IDENTITY, margin) {
+                contacts.0.push(Contact::new(ent, None, manifold, friction));
+            }
+        }
+    }
+}
+
+//one XPBD step on a manifold point, pushes the anchors apart along the normal while they overlap
+//returns the change in the multiplier
+pub fn solve_contact_point(point: &ContactPoint, normal: Vec2, a: &mut SolverBody, b: &mut SolverBody, lambda: f32, compliance: f32, delta: f32) -> f32 {
+    let r_a = world_offset(a.transform, point.local_a);
+    let r_b = world_offset(b.transform, point.local_b);
+    //positive while the surfaces overlap along the normal
+    let depth = ((a.transform.translation.truncate() + r_a) - (b.transform.translation.truncate() + r_b)).dot(normal);
+    if depth <= 0. {
+        return 0.;
+    }
+
+    let w_sum = a.mass.generalized_inverse_mass(r_a, normal) + b.mass.generalized_inverse_mass(r_b, normal);
+    if w_sum < EPSILON {
+        return 0.;
+    }
+    let d_lambda = delta_lambda(-depth, w_sum, lambda, compliance, delta);
+
+    let impulse = normal * d_lambda;
+    a.mass.apply_correction(a.transform, -impulse, r_a);
+    b.mass.apply_correction(b.transform, impulse, r_b);
+    return d_lambda;
+}
+
+//normal for every point of the contact, then static friction
+//friction is limited by the whole contact's normal force, one point holding a sliding body has to hold all of it
+fn solve_contact(contact: &mut Contact, a: &mut SolverBody, b: &mut SolverBody, delta: f32) {
+    let manifold = contact.manifold;
+    for (i, point) in manifold.points().iter().enumerate() {
+        contact.lambda[i] += solve_contact_point(point, manifold.normal, a, b, contact.lambda[i], contact.compliance, delta);
+    }
+    for (i, point) in manifold.points().iter().enumerate() {
+        contact.tangent_lambda[i] += solve_static_friction(
+            point,
+            manifold.normal,
+            a,
+            b,
+            contact.lambda.iter().sum(),
+            contact.tangent_lambda.iter().sum(),
+            contact.friction.static_coefficient,
+        );
+    }
+}
+
+fn integrate_points(
+    mut q_point: Query<(&mut Point, &mut Transform)>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    for (mut point, mut transform) in q_point.iter_mut() {
+        let position = transform.translation.truncate();
+        point.last_pos = position;
+        if point.is_static() {
+            point.velo = Vec2::ZERO;
+            continue;
+        }
+        let accel = point.accel + GRAVITY;
+
+        point.velo += accel * delta;
+        point.velo *= (1. - config.point_damping * delta).max(0.);
+
+        transform.translation = (position + point.velo * delta).extend(transform.translation.z);
+    }
+}
+
+fn solve_contacts(
+    mut q_body: Query<BodyParts, Or<(IsBody, With<Collider>)>>,
+    mut contacts: ResMut<Contacts>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    for contact in contacts.0.iter_mut() {
+        match contact.b {
+            Some(b) => {
+                let Ok([(mut trans_a, point_a, body_a), (mut trans_b, point_b, body_b)]) = q_body.get_many_mut([contact.a, b]) else {
+                    continue;
+                };
+                let mut a = SolverBody::new(&mut trans_a, point_a, body_a);
+                let mut b = SolverBody::new(&mut trans_b, point_b, body_b);
+                solve_contact(contact, &mut a, &mut b, delta);
+            }
+            None => {
+                let Ok((mut trans_a, point_a, body_a)) = q_body.get_mut(contact.a) else {
+                    continue;
+                };
+                let mut wall_transform = Transform::IDENTITY;
+                let mut a = SolverBody::new(&mut trans_a, point_a, body_a);
+                let mut b = SolverBody::new(&mut wall_transform, None, None);
+                solve_contact(contact, &mut a, &mut b, delta);
+            }
+        }
+    }
+}
+
+//backstop for points that got past the wall contacts
+fn solve_bounds(
+    mut q_point: Query<(&Point, &mut Transform), Without<RigidBody>>,
+    broadphase: Res<EntityBroadphase>,
+) {
+    let bounds = broadphase.bounds;
+    let min = Vec2::new(bounds.left, bounds.bottom);
+    let max = Vec2::new(bounds.right, bounds.top);
+
+    for (point, mut transform) in q_point.iter_mut() {
+        if point.is_static() {
+            continue;
+        }
+        //the walls are static so the whole correction goes to the point
+        let rad = Vec2::splat(point.radius);
+        let pos = transform.translation.truncate().clamp(min + rad, max - rad);
+        transform.translation = pos.extend(transform.translation.z);
+    }
+}
+
+fn update_velocities(
+    mut q_point: Query<(&mut Point, &Transform)>,
+    config: Res<SolverConfig>,
+) {
+    let delta = config.substep_dt();
+    if delta < EPSILON {
+        return;
+    }
+    for (mut point, transform) in q_point.iter_mut() {
+        point.velo = (transform.translation.truncate() - point.last_pos) / delta;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //velocity of a point kicked by accel for one step, split into substeps
+    fn kicked_velocity(substeps: usize) -> Vec2 {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(XpbdPlugin);
+        app.insert_resource(SolverConfig { substeps: substeps, point_damping: 0., ..default() });
+        let point = app.world.spawn((Point::new(Vec2::new(500., 0.)), Transform::default())).id();
+
+        app.world.run_schedule(FixedUpdate);
+        return app.world.get::<Point>(point).unwrap().velo;
+    }
+
+    //a body pushed and turned for one step, split into substeps
+    fn pushed_body(substeps: usize) -> (Vec2, f32) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugins(XpbdPlugin);
+        app.insert_resource(SolverConfig { substeps: substeps, linear_damping: 0., angular_damping: 0., ..default() });
+        let mut body = RigidBody::new(2., 4.);
+        body.force = Vec2::new(1000., 0.);
+        body.torque = 40.;
+        let body = app.world.spawn((body, Transform::default())).id();
+
+        app.world.run_schedule(FixedUpdate);
+        let body = app.world.get::<RigidBody>(body).unwrap();
+        assert_eq!((body.force, body.torque), (Vec2::ZERO, 0.));
+        return (body.velo, body.angular_velo);
+    }
+
+    #[test]
+    fn forces_last_the_whole_step() {
+        let dt = SolverConfig::default().dt;
+        for substeps in [1, 4, 8] {
+            let (velo, angular_velo) = pushed_body(substeps);
+            assert!(velo.distance(Vec2::new(500., GRAVITY.y) * dt) < 1e-3, "{substeps} substeps: {velo}");
+            assert!((angular_velo - 10. * dt).abs() < 1e-3, "{substeps} substeps: {angular_velo}");
+        }
+    }
+
+    #[test]
+    fn accel_lasts_the_whole_step() {
+        let dt = SolverConfig::default().dt;
+        for substeps in [1, 4, 8] {
+            let velo = kicked_velocity(substeps);
+            assert!(velo.distance(Vec2::new(500., GRAVITY.y) * dt) < 1e-3, "{substeps} substeps: {velo}");
+        }
+    }
+}
@@ -0,0 +1,609 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use bevy::prelude::*;
+
+use super::{world_offset, Collider, Shape, EPSILON};
+
+/*
+    ---
+    SOURCES
+    https://box2d.org/files/ErinCatto_GJK_GDC2010.pdf
+    https://dyn4j.org/2010/05/epa-expanding-polytope-algorithm/
+    https://github.com/erincatto/box2d/blob/v2.4.1/src/collision/b2_collide_polygon.cpp
+    ---
+*/
+
+const MAX_ITERATIONS: usize = 32;
+//how much better the second shape's axis has to be before it becomes the reference face, avoids flip flopping
+const REFERENCE_TOLERANCE: f32 = 0.005;
+//cos of the largest angle between the closest points and a face normal that still counts as resting on the face
+const FACE_ALIGNMENT: f32 = 0.999;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ContactPoint {
+    //on each shape's surface, relative to its body's origin and in its local space
+    pub local_a: Vec2,
+    pub local_b: Vec2,
+    //negative while the shapes are still apart
+    pub depth: f32,
+    //which features touch, stays the same while they keep touching
+    pub id: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Manifold {
+    //world space, from a towards b
+    pub normal: Vec2,
+    points: [ContactPoint; 2],
+    count: usize,
+}
+
+impl Manifold {
+    pub fn points(&self) -> &[ContactPoint] {
+        &self.points[..self.count]
+    }
+
+    fn push(&mut self, point: ContactPoint) {
+        if self.count < 2 {
+            self.points[self.count] = point;
+            self.count += 1;
+        }
+    }
+}
+
+//a shape in world space as a convex core and a rounding radius, circles and capsules are a point and a segment
+#[derive(Debug, Clone)]
+struct Hull {
+    vertices: Vec<Vec2>,
+    //outward normal of the edge from vertices[i] to vertices[i + 1]
+    normals: Vec<Vec2>,
+    radius: f32,
+}
+
+impl Hull {
+    //None for a hand built polygon without vertices, there's nothing to collide with
+    fn new(collider: &Collider, transform: &Transform) -> Option<Self> {
+        let pos = transform.translation.truncate();
+        let to_world = |v: Vec2| pos + world_offset(transform, v);
+        let (mut vertices, radius): (Vec<Vec2>, f32) = match &collider.shape {
+            Shape::Circle { radius } => (vec![pos], *radius),
+            Shape::Box { half_size } => (vec![
+                to_world(Vec2::new(-half_size.x, -half_size.y)),
+                to_world(Vec2::new(half_size.x, -half_size.y)),
+                to_world(Vec2::new(half_size.x, half_size.y)),
+                to_world(Vec2::new(-half_size.x, half_size.y)),
+            ], 0.),
+            Shape::Capsule { half_length, radius } => (vec![
+                to_world(Vec2::new(-half_length, 0.)),
+                to_world(Vec2::new(*half_length, 0.)),
+            ], *radius),
+            Shape::Polygon { vertices } => (vertices.iter().map(|v| to_world(*v)).collect(), 0.),
+            Shape::Segment { a, b } => (vec![to_world(*a), to_world(*b)], 0.),
+        };
+        if vertices.is_empty() {
+            return None;
+        }
+        //the normals below point out of counter clockwise polygons, a clockwise one gets turned around
+        let winding: f32 = (0..vertices.len()).map(|i| vertices[i].perp_dot(vertices[(i + 1) % vertices.len()])).sum();
+        if winding < 0. {
+            vertices.reverse();
+        }
+
+        //a point has no faces, a segment gets one on either side
+        let normals = if vertices.len() < 2 {
+            Vec::new()
+        } else {
+            (0..vertices.len())
+                .map(|i| {
+                    let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+                    Vec2::new(edge.y, -edge.x).normalize_or_zero()
+                })
+                .collect()
+        };
+        Some(Self { vertices: vertices, normals: normals, radius: radius })
+    }
+
+    fn support(&self, dir: Vec2) -> usize {
+        let mut best = 0;
+        let mut best_dot = f32::MIN;
+        for (i, v) in self.vertices.iter().enumerate() {
+            let dot = v.dot(dir);
+            if dot > best_dot {
+                best = i;
+                best_dot = dot;
+            }
+        }
+        return best;
+    }
+}
+
+/*
+    -------------------------------------
+        GJK / EPA
+    -------------------------------------
+*/
+
+//a point of the minkowski difference a - b and the vertices it came from
+#[derive(Debug, Clone, Copy)]
+struct SimplexVertex {
+    w: Vec2,
+    a: Vec2,
+    b: Vec2,
+    index_a: usize,
+    index_b: usize,
+}
+
+fn support(a: &Hull, b: &Hull, dir: Vec2) -> SimplexVertex {
+    let index_a = a.support(dir);
+    let index_b = b.support(-dir);
+    SimplexVertex {
+        w: a.vertices[index_a] - b.vertices[index_b],
+        a: a.vertices[index_a],
+        b: b.vertices[index_b],
+        index_a: index_a,
+        index_b: index_b,
+    }
+}
+
+//closest points of the two cores
+struct Closest {
+    point_a: Vec2,
+    point_b: Vec2,
+    distance: f32,
+    id: u32,
+}
+
+//closest point on segment v1 v2 to the origin as weights of v1 and v2
+fn segment_weights(w1: Vec2, w2: Vec2) -> (f32, f32) {
+    let edge = w2 - w1;
+    let length_squared = edge.length_squared();
+    if length_squared < EPSILON * EPSILON {
+        return (1., 0.);
+    }
+    let t = (-w1.dot(edge) / length_squared).clamp(0., 1.);
+    return (1. - t, t);
+}
+
+//the cores' closest points, or the simplex that ended up around the origin when they overlap
+fn gjk(a: &Hull, b: &Hull) -> Result<Closest, Vec<SimplexVertex>> {
+    let mut simplex = vec![support(a, b, b.vertices[0] - a.vertices[0])];
+    let mut weights = vec![1.];
+
+    for _ in 0..MAX_ITERATIONS {
+        //shrink the simplex to the part closest to the origin
+        match simplex.len() {
+            2 => {
+                let (u, v) = segment_weights(simplex[0].w, simplex[1].w);
+                if v == 0. {
+                    simplex.truncate(1);
+                    weights = vec![1.];
+                } else if u == 0. {
+                    simplex.remove(0);
+                    weights = vec![1.];
+                } else {
+                    weights = vec![u, v];
+                }
+            }
+            3 => {
+                let [w1, w2, w3] = [simplex[0].w, simplex[1].w, simplex[2].w];
+                let area = (w2 - w1).perp_dot(w3 - w1);
+                let inside = area.abs() > EPSILON * EPSILON
+                    && [(w1, w2), (w2, w3), (w3, w1)].iter().all(|(p, q)| (*q - *p).perp_dot(-*p) * area >= 0.);
+                if inside {
+                    return Err(simplex);
+                }
+
+                //the closest of the three edges
+                let mut best = (f32::MAX, 0, (1., 0.));
+                for i in 0..3 {
+                    let (p, q) = (simplex[i].w, simplex[(i + 1) % 3].w);
+                    let (u, v) = segment_weights(p, q);
+                    let distance = (p * u + q * v).length_squared();
+                    if distance < best.0 {
+                        best = (distance, i, (u, v));
+                    }
+                }
+                let (_, i, (u, v)) = best;
+                simplex = vec![simplex[i], simplex[(i + 1) % 3]];
+                weights = vec![u, v];
+                if v == 0. {
+                    simplex.truncate(1);
+                    weights = vec![1.];
+                } else if u == 0. {
+                    simplex.remove(0);
+                    weights = vec![1.];
+                }
+            }
+            _ => {}
+        }
+
+        let closest: Vec2 = simplex.iter().zip(weights.iter()).map(|(vertex, weight)| vertex.w * *weight).sum();
+        if closest.length_squared() < EPSILON * EPSILON {
+            return Err(simplex);
+        }
+
+        //stop once the next support point doesn't get any closer
+        let next = support(a, b, -closest);
+        let progress = closest.length_squared() - next.w.dot(closest);
+        let repeated = simplex.iter().any(|vertex| vertex.index_a == next.index_a && vertex.index_b == next.index_b);
+        if repeated || progress <= EPSILON * closest.length_squared() {
+            break;
+        }
+        simplex.push(next);
+    }
+
+    let point_a: Vec2 = simplex.iter().zip(weights.iter()).map(|(vertex, weight)| vertex.a * *weight).sum();
+    let point_b: Vec2 = simplex.iter().zip(weights.iter()).map(|(vertex, weight)| vertex.b * *weight).sum();
+    return Ok(Closest {
+        point_a: point_a,
+        point_b: point_b,
+        distance: point_a.distance(point_b),
+        id: feature_id(&simplex[0], simplex.get(1)),
+    });
+}
+
+fn feature_id(first: &SimplexVertex, second: Option<&SimplexVertex>) -> u32 {
+    let second = second.unwrap_or(first);
+    (first.index_a as u32 & 0xff) << 24 | (first.index_b as u32 & 0xff) << 16 | (second.index_a as u32 & 0xff) << 8 | (second.index_b as u32 & 0xff)
+}
+
+//grows the simplex out to the edge of the minkowski difference nearest the origin
+//returns the points on each core, the direction b has to move in and how far
+fn epa(a: &Hull, b: &Hull, mut polytope: Vec<SimplexVertex>) -> Option<(Vec2, Vec2, Vec2, f32, u32)> {
+    //degenerate simplices from touching shapes, push out sideways until there's a triangle
+    if polytope.len() == 1 {
+        let next = support(a, b, if polytope[0].w == Vec2::ZERO { Vec2::X } else { -polytope[0].w });
+        polytope.push(next);
+    }
+    if polytope.len() == 2 {
+        let edge = polytope[1].w - polytope[0].w;
+        let side = edge.perp();
+        let mut next = support(a, b, side);
+        if (next.w - polytope[0].w).perp_dot(edge).abs() < EPSILON {
+            next = support(a, b, -side);
+        }
+        if (next.w - polytope[0].w).perp_dot(edge).abs() < EPSILON {
+            return None;
+        }
+        polytope.push(next);
+    }
+
+    //counter clockwise, so an edge's outward normal is its right hand side
+    if (polytope[1].w - polytope[0].w).perp_dot(polytope[2].w - polytope[0].w) < 0. {
+        polytope.swap(1, 2);
+    }
+
+    let mut best = (Vec2::ZERO, 0., 0);
+    for _ in 0..MAX_ITERATIONS {
+        best = (Vec2::ZERO, f32::MAX, 0);
+        for i in 0..polytope.len() {
+            let edge = polytope[(i + 1) % polytope.len()].w - polytope[i].w;
+            let normal = Vec2::new(edge.y, -edge.x).normalize_or_zero();
+            let distance = normal.dot(polytope[i].w);
+            if normal != Vec2::ZERO && distance < best.1 {
+                best = (normal, distance, i);
+            }
+        }
+
+        let next = support(a, b, best.0);
+        let repeated = polytope.iter().any(|vertex| vertex.index_a == next.index_a && vertex.index_b == next.index_b);
+        if repeated || next.w.dot(best.0) - best.1 < EPSILON {
+            break;
+        }
+        polytope.insert(best.2 + 1, next);
+    }
+
+    let (normal, depth, i) = best;
+    let (first, second) = (polytope[i], polytope[(i + 1) % polytope.len()]);
+    let (u, v) = segment_weights(first.w, second.w);
+    let point_a = first.a * u + second.a * v;
+    let point_b = first.b * u + second.b * v;
+    return Some((point_a, point_b, normal, depth.max(0.), feature_id(&first, Some(&second))));
+}
+
+/*
+    -------------------------------------
+        SAT
+    -------------------------------------
+*/
+
+//the face of a whose outward normal separates the cores the most, and by how much
+fn max_separation(a: &Hull, b: &Hull) -> (f32, usize) {
+    let mut best = (f32::MIN, 0);
+    for (i, normal) in a.normals.iter().enumerate() {
+        let vertex = a.vertices[i];
+        let separation = b.vertices.iter().map(|v| normal.dot(*v - vertex)).fold(f32::MAX, f32::min);
+        if separation > best.0 {
+            best = (separation, i);
+        }
+    }
+    return best;
+}
+
+//keeps the part of the segment behind the plane normal . p = offset
+fn clip_segment(points: [(Vec2, usize); 2], normal: Vec2, offset: f32) -> Option<[(Vec2, usize); 2]> {
+    let d0 = normal.dot(points[0].0) - offset;
+    let d1 = normal.dot(points[1].0) - offset;
+    if d0 > 0. && d1 > 0. {
+        return None;
+    }
+    if d0 <= 0. && d1 <= 0. {
+        return Some(points);
+    }
+
+    //one end is in front, it moves onto the plane and keeps its id
+    let t = d0 / (d0 - d1);
+    let cut = points[0].0 + (points[1].0 - points[0].0) * t;
+    if d0 > 0. {
+        return Some([(cut, points[0].1), points[1]]);
+    }
+    return Some([points[0], (cut, points[1].1)]);
+}
+
+//up to two points from the incident edge of other clipped against reference's face
+//points are returned as (on reference, on incident, depth, id) with normal leaving the reference face
+fn clip_faces(reference: &Hull, face: usize, other: &Hull, margin: f32) -> Vec<(Vec2, Vec2, f32, u32)> {
+    let normal = reference.normals[face];
+    let v1 = reference.vertices[face];
+    let v2 = reference.vertices[(face + 1) % reference.vertices.len()];
+
+    //the edge of other facing most against the normal
+    let incident = (0..other.normals.len())
+        .min_by(|i, j| other.normals[*i].dot(normal).total_cmp(&other.normals[*j].dot(normal)))
+        .unwrap_or(0);
+    let next = (incident + 1) % other.vertices.len();
+    let segment = [(other.vertices[incident], incident), (other.vertices[next], next)];
+
+    let tangent = (v2 - v1).normalize_or_zero();
+    let Some(segment) = clip_segment(segment, -tangent, -tangent.dot(v1)) else {
+        return Vec::new();
+    };
+    let Some(segment) = clip_segment(segment, tangent, tangent.dot(v2)) else {
+        return Vec::new();
+    };
+
+    let radius = reference.radius + other.radius;
+    let mut points = Vec::with_capacity(2);
+    for (point, index) in segment {
+        let separation = normal.dot(point - v1);
+        if separation > radius + margin {
+            continue;
+        }
+        let on_reference = point + normal * (reference.radius - separation);
+        let on_incident = point - normal * other.radius;
+        points.push((on_reference, on_incident, radius - separation, (face as u32) << 8 | index as u32));
+    }
+    return points;
+}
+
+/*
+    -------------------------------------
+        FUNCTIONS
+    -------------------------------------
+*/
+
+//contact between two colliders, including ones up to margin apart so the solver can catch them closing in
+pub fn collide(collider_a: &Collider, transform_a: &Transform, collider_b: &Collider, transform_b: &Transform, margin: f32) -> Option<Manifold> {
+    let (Some(a), Some(b)) = (Hull::new(collider_a, transform_a), Hull::new(collider_b, transform_b)) else {
+        return None;
+    };
+    let radius = a.radius + b.radius;
+
+    //each contact point goes back into body space so it follows the bodies through the substeps
+    let to_local = |transform: &Transform, p: Vec2| {
+        (transform.rotation.inverse() * (p - transform.translation.truncate()).extend(0.)).truncate()
+    };
+    let mut manifold = Manifold::default();
+    let single = |manifold: &mut Manifold, core_a: Vec2, core_b: Vec2, normal: Vec2, depth: f32, id: u32| {
+        manifold.normal = normal;
+        manifold.push(ContactPoint {
+            local_a: to_local(transform_a, core_a + normal * a.radius),
+            local_b: to_local(transform_b, core_b - normal * b.radius),
+            depth: depth,
+            id: id,
+        });
+    };
+
+    //two circles, no need for anything general
+    if a.vertices.len() == 1 && b.vertices.len() == 1 {
+        let diff = b.vertices[0] - a.vertices[0];
+        let distance = diff.length();
+        if distance > radius + margin {
+            return None;
+        }
+        let normal = if distance > EPSILON { diff / distance } else { Vec2::Y };
+        single(&mut manifold, a.vertices[0], b.vertices[0], normal, radius - distance, 0);
+        return Some(manifold);
+    }
+
+    //shapes with faces on both sides get a face manifold from SAT
+    if !a.normals.is_empty() && !b.normals.is_empty() {
+        let (separation_a, face_a) = max_separation(&a, &b);
+        let (separation_b, face_b) = max_separation(&b, &a);
+        let separation = separation_a.max(separation_b);
+        if separation > radius + margin {
+            return None;
+        }
+
+        let flip = separation_b > separation_a + REFERENCE_TOLERANCE;
+        let (reference, face, other) = if flip { (&b, face_b, &a) } else { (&a, face_a, &b) };
+        let reference_normal = reference.normals[face];
+
+        //rounded cores that are apart may be touching corner to corner rather than resting on the face
+        let mut on_face = true;
+        if separation > EPSILON && radius > 0. {
+            if let Ok(closest) = gjk(&a, &b) {
+                let normal = (closest.point_b - closest.point_a) / closest.distance.max(EPSILON);
+                let face_normal = if flip { -reference_normal } else { reference_normal };
+                if normal.dot(face_normal) < FACE_ALIGNMENT {
+                    on_face = false;
+                    if closest.distance > radius + margin {
+                        return None;
+                    }
+                    single(&mut manifold, closest.point_a, closest.point_b, normal, radius - closest.distance, closest.id);
+                }
+            }
+        }
+
+        if on_face {
+            manifold.normal = if flip { -reference_normal } else { reference_normal };
+            for (on_reference, on_incident, depth, id) in clip_faces(reference, face, other, margin) {
+                let (point_a, point_b) = if flip { (on_incident, on_reference) } else { (on_reference, on_incident) };
+                manifold.push(ContactPoint {
+                    local_a: to_local(transform_a, point_a),
+                    local_b: to_local(transform_b, point_b),
+                    depth: depth,
+                    id: id | (flip as u32) << 16,
+                });
+            }
+        }
+        return if manifold.count > 0 { Some(manifold) } else { None };
+    }
+
+    //a circle against anything else, one point from the closest points or the penetration
+    match gjk(&a, &b) {
+        Ok(closest) => {
+            if closest.distance > radius + margin {
+                return None;
+            }
+            let normal = (closest.point_b - closest.point_a) / closest.distance.max(EPSILON);
+            single(&mut manifold, closest.point_a, closest.point_b, normal, radius - closest.distance, closest.id);
+        }
+        Err(simplex) => {
+            let (core_a, core_b, normal, depth, id) = epa(&a, &b, simplex)?;
+            single(&mut manifold, core_a, core_b, normal, radius + depth, id);
+        }
+    }
+    return Some(manifold);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-3;
+
+    fn at(x: f32, y: f32) -> Transform {
+        Transform::from_xyz(x, y, 0.)
+    }
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < TOLERANCE
+    }
+
+    #[test]
+    fn circle_vs_circle() {
+        let circle = Collider::circle(5.);
+        let manifold = collide(&circle, &at(0., 0.), &circle, &at(8., 0.), 0.).unwrap();
+        assert!(close(manifold.normal, Vec2::X));
+        assert_eq!(manifold.points().len(), 1);
+        let point = manifold.points()[0];
+        assert!((point.depth - 2.).abs() < TOLERANCE);
+        assert!(close(point.local_a, Vec2::new(5., 0.)) && close(point.local_b, Vec2::new(-5., 0.)));
+    }
+
+    #[test]
+    fn box_resting_on_box() {
+        let (floor, crate_) = (Collider::cuboid(Vec2::new(20., 5.)), Collider::cuboid(Vec2::new(5., 5.)));
+        let manifold = collide(&floor, &at(0., 0.), &crate_, &at(0., 9.9), 0.).unwrap();
+        assert!(close(manifold.normal, Vec2::Y));
+        assert_eq!(manifold.points().len(), 2);
+        assert!(manifold.points().iter().all(|point| (point.depth - 0.1).abs() < TOLERANCE));
+
+        //the same corners keep their ids as the crate slides and settles
+        let ids = |manifold: &Manifold| {
+            let mut ids: Vec<u32> = manifold.points().iter().map(|point| point.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        let moved = collide(&floor, &at(0., 0.), &crate_, &at(0.3, 9.95), 0.).unwrap();
+        assert_eq!(ids(&manifold), ids(&moved));
+        assert_ne!(moved.points()[0].id, moved.points()[1].id);
+    }
+
+    #[test]
+    fn capsule_vs_box_corner() {
+        //a box stood on its corner under a flat capsule
+        let corner = Collider::cuboid(Vec2::splat(5.));
+        let tilted = at(0., 0.).with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4));
+        let capsule = Collider::capsule(10., 2.);
+        let manifold = collide(&corner, &tilted, &capsule, &at(0., 8.5), 0.).unwrap();
+
+        assert!(close(manifold.normal, Vec2::Y));
+        assert_eq!(manifold.points().len(), 1);
+        let point = manifold.points()[0];
+        assert!((point.depth - (2. - (8.5 - 5. * 2f32.sqrt()))).abs() < TOLERANCE);
+        //the corner itself on the box, the bottom of the capsule above it
+        assert!(close(tilted.rotation.mul_vec3(point.local_a.extend(0.)).truncate(), Vec2::new(0., 5. * 2f32.sqrt())));
+        assert!(close(point.local_b, Vec2::new(0., -2.)));
+    }
+
+    #[test]
+    fn circle_deep_inside_polygon() {
+        //the circle's center is inside the square, so this goes through EPA
+        let square = Collider::polygon(&[Vec2::new(-10., -10.), Vec2::new(10., -10.), Vec2::new(10., 10.), Vec2::new(-10., 10.)]).unwrap();
+        let manifold = collide(&square, &at(0., 0.), &Collider::circle(2.), &at(1., 6.), 0.).unwrap();
+        assert!(close(manifold.normal, Vec2::Y));
+        assert_eq!(manifold.points().len(), 1);
+        assert!((manifold.points()[0].depth - 6.).abs() < TOLERANCE);
+        assert!(close(manifold.points()[0].local_a, Vec2::new(1., 10.)));
+    }
+
+    #[test]
+    fn segment_vs_circle() {
+        let segment = Collider::segment(Vec2::new(-10., 0.), Vec2::new(10., 0.));
+        let circle = Collider::circle(3.);
+        let above = collide(&segment, &at(0., 0.), &circle, &at(4., 2.), 0.).unwrap();
+        assert!(close(above.normal, Vec2::Y));
+        assert!((above.points()[0].depth - 1.).abs() < TOLERANCE);
+        assert!(close(above.points()[0].local_a, Vec2::new(4., 0.)));
+
+        //segments are two sided
+        let below = collide(&segment, &at(0., 0.), &circle, &at(-4., -2.), 0.).unwrap();
+        assert!(close(below.normal, -Vec2::Y));
+        assert!((below.points()[0].depth - 1.).abs() < TOLERANCE);
+
+        //and end at their end points
+        assert!(collide(&segment, &at(0., 0.), &circle, &at(14., 0.), 0.).is_none());
+    }
+
+    #[test]
+    fn separated_shapes_within_the_margin() {
+        let pairs = [
+            (Collider::circle(5.), Collider::circle(5.)),
+            (Collider::cuboid(Vec2::splat(5.)), Collider::cuboid(Vec2::splat(5.))),
+            (Collider::cuboid(Vec2::splat(5.)), Collider::circle(5.)),
+            (Collider::capsule(5., 5.), Collider::cuboid(Vec2::splat(5.))),
+        ];
+        for (a, b) in pairs.iter() {
+            //a one unit gap along y
+            let (ta, tb) = (at(0., 0.), at(0., 11.));
+            assert!(collide(a, &ta, b, &tb, 0.5).is_none(), "{:?} vs {:?} outside the margin", a.shape, b.shape);
+            let manifold = collide(a, &ta, b, &tb, 2.).unwrap_or_else(|| panic!("{:?} vs {:?} inside the margin", a.shape, b.shape));
+            assert!(close(manifold.normal, Vec2::Y));
+            assert!(manifold.points().iter().all(|point| (point.depth + 1.).abs() < TOLERANCE));
+        }
+    }
+
+    #[test]
+    fn hand_built_polygons() {
+        let circle = Collider::circle(2.);
+        //nothing to hit, but no panic either
+        let empty = Collider::new(Shape::Polygon { vertices: Vec::new() });
+        assert!(collide(&empty, &at(0., 0.), &circle, &at(0., 0.), 1.).is_none());
+        assert!(collide(&circle, &at(0., 0.), &empty, &at(0., 0.), 1.).is_none());
+
+        //clockwise gets the same outward normals as counter clockwise
+        let corners = [Vec2::new(-10., -10.), Vec2::new(10., -10.), Vec2::new(10., 10.), Vec2::new(-10., 10.)];
+        let ccw = Collider::new(Shape::Polygon { vertices: corners.to_vec() });
+        let cw = Collider::new(Shape::Polygon { vertices: corners.iter().rev().copied().collect() });
+        let box_ = Collider::cuboid(Vec2::splat(5.));
+        for other in [&circle, &box_] {
+            let expected = collide(&ccw, &at(0., 0.), other, &at(3., 11.5), 0.).unwrap();
+            let manifold = collide(&cw, &at(0., 0.), other, &at(3., 11.5), 0.).unwrap();
+            assert!(close(manifold.normal, Vec2::Y));
+            assert!(close(manifold.normal, expected.normal));
+            assert_eq!(manifold.points().len(), expected.points().len());
+        }
+    }
+}